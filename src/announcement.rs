@@ -0,0 +1,94 @@
+use std::{sync::Arc, time::Duration};
+
+use serenity::{client::Context, prelude::RwLock};
+use tracing::warn;
+
+use crate::{
+    commands::{
+        hostgame::session_message_content,
+        status::{get_announcement_embed, get_summary_embed},
+    },
+    context_ext::ContextExt,
+    session::Session,
+};
+
+// Changes made within this long of each other are sent to Discord as a single edit
+const UPDATE_DELAY: Duration = Duration::from_secs(3);
+
+/// Re-renders the session's announcement message right away
+pub async fn update_announcement(ctx: &Context, session: &Arc<RwLock<Session>>) {
+    let guild_id = ctx.config().await.guild_id;
+    let embed = get_announcement_embed(ctx, session, guild_id).await;
+    let (channel_id, message_id, content) = {
+        let session = session.read().await;
+        let content = session_message_content(
+            session.game.role_id,
+            session.time,
+            &session.description,
+            session.temp_voice_channel,
+        );
+        (session.channel_id(), session.message_id, content)
+    };
+
+    if let Err(why) = ctx
+        .discord()
+        .await
+        .edit_message(channel_id, message_id, |message| {
+            message.content(content).set_embed(embed)
+        })
+        .await
+    {
+        warn!("Error updating session message: {}", why);
+    }
+}
+
+/// Re-renders the announcement message after a short delay, so a burst of RSVPs
+/// results in a single edit instead of running into rate limits
+pub async fn schedule_announcement_update(ctx: &Context, session: Arc<RwLock<Session>>) {
+    {
+        let mut session = session.write().await;
+        if session.announcement_update_pending {
+            return;
+        }
+        session.announcement_update_pending = true;
+    }
+
+    let ctx = ctx.clone();
+    tokio::task::spawn(async move {
+        tokio::time::sleep(UPDATE_DELAY).await;
+        session.write().await.announcement_update_pending = false;
+
+        // The session may have ended while waiting, in which case its message is already final
+        let channel_id = session.read().await.channel_id();
+        match ctx.session(channel_id).await {
+            Some(current) if Arc::ptr_eq(&current, &session) => {
+                update_announcement(&ctx, &session).await
+            }
+            _ => {}
+        }
+    });
+}
+
+/// Replaces the announcement with a summary of the session and removes its buttons,
+/// `action` being whether it was "ended" or "cancelled"
+pub async fn finalize_announcement(ctx: &Context, session: &Arc<RwLock<Session>>, action: &str) {
+    let guild_id = ctx.config().await.guild_id;
+    let embed = get_summary_embed(ctx, session, guild_id, action).await;
+    let (channel_id, message_id) = {
+        let session = session.read().await;
+        (session.channel_id(), session.message_id)
+    };
+
+    if let Err(why) = ctx
+        .discord()
+        .await
+        .edit_message(channel_id, message_id, |message| {
+            message
+                .set_embed(embed)
+                .components(|components| components.set_action_rows(vec![]))
+        })
+        .await
+    {
+        warn!("Error finalizing session message: {}", why);
+    }
+}
@@ -0,0 +1,124 @@
+use std::sync::Arc;
+
+use chrono::{DateTime, Duration, Utc};
+use serenity::{
+    client::Context,
+    model::{
+        id::{ChannelId, UserId},
+        voice::VoiceState,
+    },
+    prelude::RwLock,
+};
+
+use crate::{
+    clock,
+    context_ext::ContextExt,
+    session::{Session, UserState},
+};
+
+/// The users currently in `channel_id` according to the cache, or None if the guild isn't cached yet
+pub async fn voice_members(ctx: &Context, channel_id: ChannelId) -> Option<Vec<UserId>> {
+    let guild_id = ctx.config().await.guild_id;
+    ctx.discord()
+        .await
+        .voice_members(guild_id, channel_id)
+        .await
+}
+
+/// Brings a started session's attendance in line with who is in the voice channel right now,
+/// for when the bot missed the voice updates (at the start of the session or after a restart)
+pub async fn sync_attendance(ctx: &Context, session: &Arc<RwLock<Session>>) {
+    if !session.read().await.is_started() {
+        return;
+    }
+
+    let config = ctx.config().await;
+    let vc_channel = session.read().await.voice_channel(&config);
+    let members = match voice_members(ctx, vc_channel).await {
+        Some(members) => members,
+        None => return,
+    };
+
+    let now = clock::now();
+    let mut session = session.write().await;
+    let tracked = session.attendance.keys().copied().collect::<Vec<_>>();
+    for user_id in tracked {
+        if !members.contains(&user_id) {
+            session.leave_voice(user_id, now);
+        }
+    }
+    for user_id in members {
+        session.join_voice(user_id, now);
+    }
+}
+
+/// Records a user joining or leaving the voice channel of the sessions which have started
+pub async fn record_voice_state(ctx: &Context, state: &VoiceState) {
+    let config = ctx.config().await;
+    let now = clock::now();
+    let mut changed = false;
+
+    for session in ctx.sessions().await {
+        let mut session = session.write().await;
+        if !session.is_started() {
+            continue;
+        }
+
+        let vc_channel = session.voice_channel(&config);
+        changed |= if state.channel_id == Some(vc_channel) {
+            session.join_voice(state.user_id, now)
+        } else {
+            session.leave_voice(state.user_id, now)
+        };
+    }
+
+    if changed {
+        ctx.save_sessions().await;
+    }
+}
+
+pub fn format_duration(duration: Duration) -> String {
+    let hours = duration.num_hours();
+    let minutes = duration.num_minutes() % 60;
+    if hours > 0 {
+        format!("{}h {}m", hours, minutes)
+    } else {
+        format!("{}m", minutes)
+    }
+}
+
+/// Who was in the voice channel during the session and for how long, and which Yes responders never showed
+pub fn attendance_report(session: &Session, now: DateTime<Utc>) -> String {
+    let mut attended = session
+        .attendance
+        .keys()
+        .map(|user_id| (*user_id, session.time_in_voice(*user_id, now)))
+        .collect::<Vec<_>>();
+    attended.sort_by_key(|(_, time)| -*time);
+
+    let no_shows = session
+        .users
+        .iter()
+        .filter(|(user_id, state)| {
+            **state == UserState::Will && !session.attendance.contains_key(user_id)
+        })
+        .map(|(user_id, _)| format!("<@{}>", user_id))
+        .collect::<Vec<_>>();
+
+    let attended = if attended.is_empty() {
+        "Nobody".to_string()
+    } else {
+        attended
+            .iter()
+            .map(|(user_id, time)| format!("<@{}> ({})", user_id, format_duration(*time)))
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+
+    let mut report = format!("Attended: {}", attended);
+    if !no_shows.is_empty() {
+        report += &format!("\nSaid Yes but never showed up: {}", no_shows.join(", "));
+    }
+
+    report
+}
@@ -0,0 +1,22 @@
+use chrono::{DateTime, Utc};
+
+/// The current time, which every timestamp the bot stores or compares against should come from
+#[cfg(not(test))]
+pub fn now() -> DateTime<Utc> {
+    Utc::now()
+}
+
+// Tests run with tokio's clock paused, so the time moves along with it instead of the wall clock,
+// which lets a test skip ahead to a session's start by sleeping
+#[cfg(test)]
+pub fn now() -> DateTime<Utc> {
+    use std::{sync::OnceLock, time::Instant};
+
+    static START: OnceLock<(DateTime<Utc>, Instant)> = OnceLock::new();
+    let (start_time, start_instant) = START.get_or_init(|| (Utc::now(), Instant::now()));
+
+    let elapsed = tokio::time::Instant::now()
+        .into_std()
+        .saturating_duration_since(*start_instant);
+    *start_time + chrono::Duration::from_std(elapsed).unwrap_or_else(|_| chrono::Duration::zero())
+}
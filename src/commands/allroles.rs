@@ -1,4 +1,5 @@
 use super::prelude::*;
+use crate::config::Capability;
 use crate::context_ext::ContextExt;
 use crate::interaction_handler::{CommandHandler, InteractionHandler};
 
@@ -15,67 +16,53 @@ impl InteractionHandler for AllRoles {
     fn name(&self) -> &'static str {
         "allroles"
     }
+
+    fn capability(&self) -> Option<Capability> {
+        Some(Capability::ManageRoles)
+    }
 }
 
 #[async_trait]
 impl CommandHandler for AllRoles {
-    async fn invoke(&self, ctx: Context, interaction: ApplicationCommandInteraction) {
-        if ctx.config().await.admins.contains(&interaction.user.id) {
-            let (user_id, _) = interaction
-                .data
-                .resolved
-                .members
-                .iter()
-                .next()
-                .expect("Error retrieving user id");
-
-            let guild_id = interaction
-                .guild_id
-                .clone()
-                .expect("Error retrieving guild_id");
-
-            match ctx.http.get_member(guild_id.0, user_id.0).await {
-                Ok(mut member) => {
-                    let mut role_vector: Vec<RoleId> = ctx
-                        .config()
-                        .await
-                        .games
-                        .iter()
-                        .filter(|game| game.all_roles_exception != Some(true))
-                        .map(|game| game.role_id)
-                        .filter(|role_id| !member.roles.contains(role_id))
-                        .collect();
+    async fn invoke(&self, ctx: Context, interaction: ApplicationCommandInteraction) -> Result<()> {
+        let (user_id, guild_id) = match (
+            interaction.data.resolved.members.keys().next(),
+            interaction.guild_id,
+        ) {
+            (Some(user_id), Some(guild_id)) => (*user_id, guild_id),
+            _ => return Err(Error::InvalidInteraction("allroles used without a member")),
+        };
 
-                    if let Some(role_id) = ctx.config().await.default_user_role {
-                        if !member.roles.contains(&role_id) {
-                            role_vector.push(role_id);
-                        }
-                    }
+        let discord = ctx.discord().await;
+        let member = discord.member(guild_id, user_id).await?;
+        let mut role_vector: Vec<RoleId> = ctx
+            .config()
+            .await
+            .games
+            .iter()
+            .filter(|game| game.all_roles_exception != Some(true))
+            .map(|game| game.role_id)
+            .filter(|role_id| !member.roles.contains(role_id))
+            .collect();
 
-                    match member.add_roles(&ctx.http, &role_vector).await {
-                        Ok(_) => {
-                            interaction_respond_with_private_message(
-                                &ctx,
-                                &Interaction::ApplicationCommand(interaction),
-                                "Roles added successfully!",
-                            )
-                            .await
-                        }
-                        Err(why) => {
-                            warn!("There was an error adding the roles: {}", why);
-                        }
-                    }
-                }
-                Err(why) => warn!("Error retrieving member: {}", why),
+        if let Some(role_id) = ctx.config().await.default_user_role {
+            if !member.roles.contains(&role_id) {
+                role_vector.push(role_id);
             }
-        } else {
-            interaction_respond_with_private_message(
-                &ctx,
-                &Interaction::ApplicationCommand(interaction),
-                "You do not have permissions to use this command!",
-            )
-            .await;
         }
+
+        for role_id in role_vector {
+            discord.add_role(guild_id, user_id, role_id).await?;
+        }
+
+        interaction_respond_with_private_message(
+            &ctx,
+            &Interaction::ApplicationCommand(interaction),
+            "Roles added successfully!",
+        )
+        .await;
+
+        Ok(())
     }
 
     fn create_command(
@@ -0,0 +1,246 @@
+use crate::{
+    clock,
+    commands::prelude::*,
+    config::{Config, Game},
+    context_ext::ContextExt,
+    ical::{to_ics, CalendarEvent},
+    interaction_handler::{CommandHandler, InteractionHandler, MessageHandler},
+    scheduler::{next_occurrence, Occurrence, ScheduleState},
+    time_parse::parse_offset,
+};
+
+use chrono::{DateTime, Duration, Utc};
+use serenity::{
+    async_trait,
+    client::Context,
+    http::AttachmentType,
+    model::{
+        id::ChannelId,
+        interactions::{
+            application_command::ApplicationCommandInteraction,
+            message_component::MessageComponentInteraction,
+        },
+    },
+};
+
+// Sessions don't have an end time, so calendar events are this many hours long
+const EVENT_HOURS: i64 = 2;
+
+// How many weeks ahead scheduled sessions are put in the calendar
+const SCHEDULE_WEEKS: i64 = 4;
+
+// Calendar apps update the event with the same uid when the file is imported again,
+// so it has to stay the same when a session is rescheduled
+fn calendar_event(
+    config: &Config,
+    game: &Game,
+    uid: String,
+    time: DateTime<Utc>,
+    description: &str,
+) -> CalendarEvent {
+    // Only the reminders sent before the start make sense as alarms
+    let alarms = config
+        .reminders_for(game)
+        .iter()
+        .filter_map(|reminder| parse_offset(&reminder.offset))
+        .filter(|offset| *offset < Duration::zero())
+        .map(|offset| -offset)
+        .collect();
+
+    CalendarEvent {
+        uid,
+        summary: format!("{} session", game.name),
+        description: description.to_string(),
+        start: time,
+        end: time + Duration::hours(EVENT_HOURS),
+        alarms,
+    }
+}
+
+// The session running in `channel_id`, if there is one
+async fn session_events(ctx: &Context, channel_id: ChannelId) -> Vec<CalendarEvent> {
+    let config = ctx.config().await;
+    match ctx.session(channel_id).await {
+        Some(session) => {
+            let session = session.read().await;
+            vec![calendar_event(
+                &config,
+                &session.game,
+                format!("session-{}@session-bot-rs", session.message_id),
+                session.time,
+                &session.description,
+            )]
+        }
+        None => vec![],
+    }
+}
+
+// The scheduled sessions which will be posted within the next few weeks
+async fn scheduled_events(ctx: &Context) -> Vec<CalendarEvent> {
+    let config = ctx.config().await;
+    let state = ctx
+        .data
+        .read()
+        .await
+        .get::<ScheduleState>()
+        .expect("Error reading schedule state from TypeMap")
+        .clone();
+    let state = state.read().await;
+    let horizon = clock::now() + Duration::weeks(SCHEDULE_WEEKS);
+
+    let mut events = vec![];
+    for schedule in &config.schedules {
+        let (game, channel_id) = match config
+            .games
+            .iter()
+            .find(|game| game.name == schedule.game)
+            .and_then(|game| game.channel_id.map(|channel_id| (game, channel_id)))
+        {
+            Some(found) => found,
+            None => continue,
+        };
+
+        let mut from = clock::now();
+        while let Some(time) = next_occurrence(schedule, from.with_timezone(&config.timezone)) {
+            if time > horizon {
+                break;
+            }
+
+            // Occurrences which were posted are running sessions, the others were skipped
+            let occurrence = Occurrence {
+                game: schedule.game.clone(),
+                time,
+            };
+            if !state.is_handled(&occurrence) {
+                events.push(calendar_event(
+                    &config,
+                    game,
+                    format!("{}-{}@session-bot-rs", channel_id, time.timestamp()),
+                    time,
+                    schedule.description.as_deref().unwrap_or_default(),
+                ));
+            }
+
+            from = time + Duration::minutes(1);
+        }
+    }
+
+    events.sort_by_key(|event| event.start);
+    events
+}
+
+#[derive(Clone, Copy)]
+pub struct CalendarCommand;
+
+impl InteractionHandler for CalendarCommand {
+    fn name(&self) -> &'static str {
+        "calendar"
+    }
+}
+
+#[async_trait]
+impl CommandHandler for CalendarCommand {
+    async fn invoke(&self, ctx: Context, interaction: ApplicationCommandInteraction) -> Result<()> {
+        let mut events = session_events(&ctx, interaction.channel_id).await;
+        events.extend(scheduled_events(&ctx).await);
+
+        if events.is_empty() {
+            interaction_respond_with_private_message(
+                &ctx,
+                &Interaction::ApplicationCommand(interaction),
+                "There are no upcoming sessions to put in a calendar!",
+            )
+            .await;
+            return Ok(());
+        }
+
+        // Files can only be sent in a followup, so the response is deferred first
+        ctx.discord()
+            .await
+            .create_interaction_response(interaction.id, &interaction.token, |response| {
+                response
+                    .kind(InteractionResponseType::DeferredChannelMessageWithSource)
+                    .interaction_response_data(|message| {
+                        message.flags(InteractionApplicationCommandCallbackDataFlags::EPHEMERAL)
+                    })
+            })
+            .await?;
+
+        let ics = to_ics(&events);
+        ctx.discord()
+            .await
+            .create_followup_message(&interaction.token, |message| {
+                message
+                    .content("Open the file to add the sessions to your calendar!")
+                    .add_file(AttachmentType::Bytes {
+                        data: ics.as_bytes().into(),
+                        filename: "sessions.ics".to_string(),
+                    })
+                    .flags(InteractionApplicationCommandCallbackDataFlags::EPHEMERAL)
+            })
+            .await?;
+
+        Ok(())
+    }
+
+    fn create_command(
+        self,
+        command: &mut serenity::builder::CreateApplicationCommand,
+    ) -> &mut serenity::builder::CreateApplicationCommand {
+        command.name(self.name()).description(
+            "Gives you this channel's session and the upcoming scheduled ones as a calendar file",
+        )
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct CalendarButton;
+
+impl InteractionHandler for CalendarButton {
+    fn name(&self) -> &'static str {
+        "button-calendar"
+    }
+}
+
+#[async_trait]
+impl MessageHandler for CalendarButton {
+    async fn invoke(&self, ctx: Context, interaction: MessageComponentInteraction) -> Result<()> {
+        let events = session_events(&ctx, interaction.channel_id).await;
+        if events.is_empty() {
+            interaction_respond_with_private_message(
+                &ctx,
+                &Interaction::MessageComponent(interaction),
+                "This session is no longer running!",
+            )
+            .await;
+            return Ok(());
+        }
+
+        ctx.discord()
+            .await
+            .create_interaction_response(interaction.id, &interaction.token, |response| {
+                response
+                    .kind(InteractionResponseType::DeferredChannelMessageWithSource)
+                    .interaction_response_data(|message| {
+                        message.flags(InteractionApplicationCommandCallbackDataFlags::EPHEMERAL)
+                    })
+            })
+            .await?;
+
+        let ics = to_ics(&events);
+        ctx.discord()
+            .await
+            .create_followup_message(&interaction.token, |message| {
+                message
+                    .content("Open the file to add the session to your calendar!")
+                    .add_file(AttachmentType::Bytes {
+                        data: ics.as_bytes().into(),
+                        filename: "session.ics".to_string(),
+                    })
+                    .flags(InteractionApplicationCommandCallbackDataFlags::EPHEMERAL)
+            })
+            .await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,260 @@
+use crate::{
+    announcement::schedule_announcement_update,
+    commands::{endhost::can_cancel_session, prelude::*, status::no_session_message},
+    config::Capability,
+    context_ext::ContextExt,
+    interaction_handler::{is_allowed, CommandHandler, InteractionHandler},
+};
+
+use serenity::{
+    async_trait,
+    client::Context,
+    model::interactions::application_command::{
+        ApplicationCommandInteraction, ApplicationCommandInteractionDataOption,
+        ApplicationCommandInteractionDataOptionValue, ApplicationCommandOptionType,
+    },
+};
+
+// The user given to a subcommand
+fn user_option(subcommand: &ApplicationCommandInteractionDataOption) -> Option<UserId> {
+    subcommand
+        .options
+        .iter()
+        .find(|option| option.name == "user")
+        .and_then(|option| match option.resolved.as_ref() {
+            Some(ApplicationCommandInteractionDataOptionValue::User(user, _)) => Some(user.id),
+            _ => None,
+        })
+}
+
+#[derive(Clone, Copy)]
+pub struct CoHost;
+
+impl InteractionHandler for CoHost {
+    fn name(&self) -> &'static str {
+        "cohost"
+    }
+}
+
+#[async_trait]
+impl CommandHandler for CoHost {
+    async fn invoke(&self, ctx: Context, interaction: ApplicationCommandInteraction) -> Result<()> {
+        let session = match ctx.session(interaction.channel_id).await {
+            Some(session) => session,
+            None => {
+                let content = no_session_message(&ctx).await;
+                interaction_respond_with_private_message(
+                    &ctx,
+                    &Interaction::ApplicationCommand(interaction),
+                    &content,
+                )
+                .await;
+                return Ok(());
+            }
+        };
+
+        let (invoker, member) = (interaction.user.id, interaction.member.as_ref());
+        if !can_cancel_session(&ctx, &*session.read().await, invoker, member).await {
+            interaction_respond_with_private_message(
+                &ctx,
+                &Interaction::ApplicationCommand(interaction),
+                "You don't have permissions to change the hosts of this session!",
+            )
+            .await;
+            return Ok(());
+        }
+
+        // Discord always sends exactly one of the subcommands, which always has its required user
+        let subcommand = interaction
+            .data
+            .options
+            .first()
+            .ok_or(Error::InvalidInteraction(
+                "cohost used without a subcommand",
+            ))?;
+        let user_id = user_option(subcommand)
+            .ok_or(Error::InvalidInteraction("cohost used without a user"))?;
+
+        let result = {
+            let mut session = session.write().await;
+            match subcommand.name.as_str() {
+                "add" if session.is_host(user_id) => {
+                    Err(format!("<@{}> is already hosting this session!", user_id))
+                }
+                "add" => {
+                    session.cohosts.push(user_id);
+                    Ok(format!("<@{}> is now co-hosting the session!", user_id))
+                }
+                _ if !session.cohosts.contains(&user_id) => {
+                    Err(format!("<@{}> is not a co-host of this session!", user_id))
+                }
+                _ => {
+                    session.cohosts.retain(|cohost| *cohost != user_id);
+                    Ok(format!(
+                        "<@{}> is no longer co-hosting the session",
+                        user_id
+                    ))
+                }
+            }
+        };
+
+        let content = match result {
+            Ok(content) => content,
+            Err(why) => {
+                interaction_respond_with_private_message(
+                    &ctx,
+                    &Interaction::ApplicationCommand(interaction),
+                    &why,
+                )
+                .await;
+                return Ok(());
+            }
+        };
+
+        ctx.save_sessions().await;
+        schedule_announcement_update(&ctx, session).await;
+
+        ctx.discord()
+            .await
+            .create_interaction_response(interaction.id, &interaction.token, |response| {
+                response
+                    .kind(InteractionResponseType::ChannelMessageWithSource)
+                    .interaction_response_data(|message| message.content(content))
+            })
+            .await?;
+
+        Ok(())
+    }
+
+    fn create_command(
+        self,
+        command: &mut serenity::builder::CreateApplicationCommand,
+    ) -> &mut serenity::builder::CreateApplicationCommand {
+        command
+            .name(self.name())
+            .description("Adds or removes a co-host, who can manage the session like the host")
+            .create_option(|option| {
+                option
+                    .kind(ApplicationCommandOptionType::SubCommand)
+                    .name("add")
+                    .description("Lets someone manage this session along with you")
+                    .create_sub_option(|option| {
+                        option
+                            .kind(ApplicationCommandOptionType::User)
+                            .name("user")
+                            .description("The new co-host")
+                            .required(true)
+                    })
+            })
+            .create_option(|option| {
+                option
+                    .kind(ApplicationCommandOptionType::SubCommand)
+                    .name("remove")
+                    .description("Stops someone from co-hosting this session")
+                    .create_sub_option(|option| {
+                        option
+                            .kind(ApplicationCommandOptionType::User)
+                            .name("user")
+                            .description("The co-host to remove")
+                            .required(true)
+                    })
+            })
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct TransferHost;
+
+impl InteractionHandler for TransferHost {
+    fn name(&self) -> &'static str {
+        "transferhost"
+    }
+}
+
+#[async_trait]
+impl CommandHandler for TransferHost {
+    async fn invoke(&self, ctx: Context, interaction: ApplicationCommandInteraction) -> Result<()> {
+        let session = match ctx.session(interaction.channel_id).await {
+            Some(session) => session,
+            None => {
+                let content = no_session_message(&ctx).await;
+                interaction_respond_with_private_message(
+                    &ctx,
+                    &Interaction::ApplicationCommand(interaction),
+                    &content,
+                )
+                .await;
+                return Ok(());
+            }
+        };
+
+        // Co-hosts can manage the session, but only the host can give it away
+        let (invoker, member) = (interaction.user.id, interaction.member.as_ref());
+        let is_host = session.read().await.host == invoker;
+        if !is_host && !is_allowed(&ctx, Some(Capability::EndAnySession), invoker, member).await {
+            interaction_respond_with_private_message(
+                &ctx,
+                &Interaction::ApplicationCommand(interaction),
+                "You don't have permissions to hand this session over!",
+            )
+            .await;
+            return Ok(());
+        }
+
+        let user_id = interaction
+            .data
+            .options
+            .iter()
+            .find(|option| option.name == "user")
+            .and_then(|option| match option.resolved.as_ref() {
+                Some(ApplicationCommandInteractionDataOptionValue::User(user, _)) => Some(user.id),
+                _ => None,
+            })
+            .ok_or(Error::InvalidInteraction(
+                "transferhost used without a user",
+            ))?;
+
+        if session.read().await.host == user_id {
+            interaction_respond_with_private_message(
+                &ctx,
+                &Interaction::ApplicationCommand(interaction),
+                &format!("<@{}> is already the host of this session!", user_id),
+            )
+            .await;
+            return Ok(());
+        }
+
+        session.write().await.transfer_host(user_id);
+        ctx.save_sessions().await;
+        schedule_announcement_update(&ctx, session).await;
+
+        ctx.discord()
+            .await
+            .create_interaction_response(interaction.id, &interaction.token, |response| {
+                response
+                    .kind(InteractionResponseType::ChannelMessageWithSource)
+                    .interaction_response_data(|message| {
+                        message.content(format!("<@{}> is now hosting the session!", user_id))
+                    })
+            })
+            .await?;
+
+        Ok(())
+    }
+
+    fn create_command(
+        self,
+        command: &mut serenity::builder::CreateApplicationCommand,
+    ) -> &mut serenity::builder::CreateApplicationCommand {
+        command
+            .name(self.name())
+            .description("Hands the session over to someone else")
+            .create_option(|option| {
+                option
+                    .kind(ApplicationCommandOptionType::User)
+                    .name("user")
+                    .description("The new host")
+                    .required(true)
+            })
+    }
+}
@@ -20,8 +20,12 @@ async fn get_select_menu_option(
     color_role: &ColorRole,
     idx: usize,
 ) -> Option<CreateSelectMenuOption> {
-    let role_id = color_role.role_id;
-    let color = role_id.to_role_cached(&ctx.cache).await?.colour;
+    let guild_id = ctx.config().await.guild_id;
+    let color = ctx
+        .discord()
+        .await
+        .role_colour(guild_id, color_role.role_id)
+        .await?;
 
     Some(
         CreateSelectMenuOption::default()
@@ -36,7 +40,7 @@ async fn get_action_row(ctx: &Context) -> CreateActionRow {
     let mut options_vec = vec![];
 
     for (idx, color_role) in ctx.config().await.colors.iter().enumerate() {
-        if let Some(option) = get_select_menu_option(&ctx, &color_role, idx).await {
+        if let Some(option) = get_select_menu_option(ctx, color_role, idx).await {
             options_vec.push(option);
         }
     }
@@ -60,7 +64,7 @@ impl InteractionHandler for ColorsCommand {
 
 #[async_trait]
 impl CommandHandler for ColorsCommand {
-    async fn invoke(&self, ctx: Context, interaction: ApplicationCommandInteraction) {
+    async fn invoke(&self, ctx: Context, interaction: ApplicationCommandInteraction) -> Result<()> {
         let action_row = get_action_row(&ctx).await;
 
         if let Some(member) = &interaction.member {
@@ -81,8 +85,9 @@ impl CommandHandler for ColorsCommand {
                 "No color role currently set, select to add one!".to_string()
             };
 
-            interaction
-                .create_interaction_response(&ctx.http, |response| {
+            ctx.discord()
+                .await
+                .create_interaction_response(interaction.id, &interaction.token, |response| {
                     response
                         .kind(InteractionResponseType::ChannelMessageWithSource)
                         .interaction_response_data(|message| {
@@ -92,9 +97,10 @@ impl CommandHandler for ColorsCommand {
                                 .flags(InteractionApplicationCommandCallbackDataFlags::EPHEMERAL)
                         })
                 })
-                .await
-                .unwrap_or_else(|why| warn!("Error responding to interaction: {}", why));
+                .await?;
         }
+
+        Ok(())
     }
 
     fn create_command(
@@ -116,40 +122,38 @@ impl InteractionHandler for MenuHandler {
 
 #[async_trait]
 impl MessageHandler for MenuHandler {
-    async fn invoke(&self, ctx: Context, interaction: MessageComponentInteraction) {
-        let index = interaction.clone().data.values[0]
-            .parse::<usize>()
-            .expect("Error parsing role data to usize");
-
-        let role_id = ctx.config().await.colors[index].role_id;
-        let mut member = interaction.member.clone().expect("Error retrieving member");
+    async fn invoke(&self, ctx: Context, interaction: MessageComponentInteraction) -> Result<()> {
         let color_roles = &ctx.config().await.colors;
-        let roles = member
-            .roles(&ctx.cache)
-            .await
-            .expect("Error retrieving roles");
-
-        let roles_to_remove: Vec<RoleId> = roles
-            .iter()
-            .filter(|role| {
-                color_roles
-                    .iter()
-                    .any(|color_role| color_role.role_id == role.id)
-            })
-            .map(|role| role.id)
-            .collect();
-
-        if let Err(why) = member.remove_roles(&ctx.http, &roles_to_remove).await {
-            warn!("Error removing roles: {}", why);
+        let role_id = color_roles[selected_index(&interaction, color_roles.len())?].role_id;
+        let (guild_id, roles) = match (interaction.guild_id, interaction.member.as_ref()) {
+            (Some(guild_id), Some(member)) => (guild_id, &member.roles),
+            _ => {
+                return Err(Error::InvalidInteraction(
+                    "Colors menu used outside of a guild",
+                ))
+            }
+        };
+        let user_id = interaction.user.id;
+
+        let discord = ctx.discord().await;
+        for role in roles {
+            if color_roles
+                .iter()
+                .any(|color_role| color_role.role_id == *role)
+            {
+                if let Err(why) = discord.remove_role(guild_id, user_id, *role).await {
+                    warn!("Error removing roles: {}", why);
+                }
+            }
         }
 
-        if let Err(why) = member.add_role(&ctx.http, role_id).await {
+        if let Err(why) = discord.add_role(guild_id, user_id, role_id).await {
             warn!("Error adding role: {}", why);
         }
 
         let action_row = get_action_row(&ctx).await;
-        interaction
-            .create_interaction_response(&ctx.http, |response| {
+        discord
+            .create_interaction_response(interaction.id, &interaction.token, |response| {
                 response
                     .kind(InteractionResponseType::UpdateMessage)
                     .interaction_response_data(|message| {
@@ -158,7 +162,8 @@ impl MessageHandler for MenuHandler {
                             .components(|components| components.add_action_row(action_row))
                     })
             })
-            .await
-            .unwrap_or_else(|why| warn!("Error responding to interaction: {}", why));
+            .await?;
+
+        Ok(())
     }
 }
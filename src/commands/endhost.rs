@@ -1,8 +1,21 @@
+use std::sync::Arc;
+
 use crate::{
-    commands::{prelude::*, status::users_with_state},
+    announcement::finalize_announcement,
+    attendance::attendance_report,
+    clock,
+    commands::{
+        prelude::*,
+        status::{no_session_message, users_with_state},
+    },
+    config::Capability,
     context_ext::ContextExt,
-    interaction_handler::{CommandHandler, InteractionHandler, MessageHandler},
+    guild_event::{set_event_status, EventStatus},
+    history::{ArchivedSession, History},
+    interaction_handler::{is_allowed, CommandHandler, InteractionHandler, MessageHandler},
     session::{Session, UserState},
+    time_parse::parse_duration,
+    voice::delete_temp_voice,
 };
 
 use serenity::{
@@ -17,7 +30,9 @@ use serenity::{
             Interaction,
         },
     },
+    prelude::RwLock,
 };
+use tracing::info;
 
 fn get_action_row() -> CreateActionRow {
     CreateActionRow::default()
@@ -36,9 +51,145 @@ fn get_action_row() -> CreateActionRow {
         .clone()
 }
 
-async fn can_cancel_session(ctx: &Context, user_id: UserId) -> bool {
-    let host = ctx.session().await.read().await.host;
-    user_id == host || ctx.config().await.admins.contains(&user_id)
+/// Whether the user is one of the session's hosts, or may end anybody's sessions
+pub async fn can_cancel_session(
+    ctx: &Context,
+    session: &Session,
+    user_id: UserId,
+    member: Option<&Member>,
+) -> bool {
+    session.is_host(user_id)
+        || is_allowed(ctx, Some(Capability::EndAnySession), user_id, member).await
+}
+
+/// Stops tracking the session, replaces its announcement with a summary and lets everyone know it's over.
+/// Returns false if the session had already been ended elsewhere
+pub async fn end_session(ctx: &Context, session: &Arc<RwLock<Session>>) -> bool {
+    if !stop_session(ctx, session).await {
+        return false;
+    }
+    wrap_up_session(ctx, session).await;
+    true
+}
+
+// Stops tracking the session first, so no pending update overwrites the final message.
+// This doesn't talk to Discord, so interactions can still be responded to in time afterwards
+async fn stop_session(ctx: &Context, session: &Arc<RwLock<Session>>) -> bool {
+    if !ctx.remove_session(session).await {
+        return false;
+    }
+    session.read().await.handle.abort();
+    ctx.save_sessions().await;
+    true
+}
+
+// Everything end_session does on Discord, once the session is no longer tracked
+async fn wrap_up_session(ctx: &Context, session: &Arc<RwLock<Session>>) {
+    let is_started = session.read().await.is_started();
+    let action = if is_started { "ended" } else { "cancelled" };
+    let game = session.read().await.game.clone();
+    let channel_id = session.read().await.channel_id();
+
+    finalize_announcement(ctx, session, action).await;
+    let message_id = session.read().await.message_id;
+    if let Err(why) = ctx
+        .discord()
+        .await
+        .unpin_message(channel_id, message_id)
+        .await
+    {
+        warn!("Error unpinning message: {}", why);
+    }
+
+    let content = if !is_started {
+        let user_pings = users_with_state(&session.read().await.users, UserState::Will);
+        if user_pings.1 == 0 {
+            "".to_string()
+        } else {
+            user_pings.0 + ": "
+        }
+    } else {
+        "".to_string()
+    };
+
+    // Everyone still in the voice channel leaves it as far as the session is concerned
+    let report = if is_started {
+        let now = clock::now();
+        let mut session = session.write().await;
+        let users = session.attendance.keys().copied().collect::<Vec<_>>();
+        for user_id in users {
+            session.leave_voice(user_id, now);
+        }
+        format!("\n{}", attendance_report(&session, now))
+    } else {
+        "".to_string()
+    };
+
+    let archived = ArchivedSession::from_session(&*session.read().await, clock::now());
+    ctx.data
+        .read()
+        .await
+        .get::<History>()
+        .expect("Error reading history from TypeMap")
+        .write()
+        .await
+        .archive(archived);
+
+    if let Err(why) = ctx
+        .discord()
+        .await
+        .send_message(channel_id, |message| {
+            message.content(format!(
+                "{}{} Session has been {}!{}",
+                content, game.name, action, report
+            ));
+            // The report mentions everyone who attended, which shouldn't ping them
+            if is_started {
+                message.allowed_mentions(|mentions| mentions.empty_parse());
+            }
+            message
+        })
+        .await
+    {
+        warn!("Error sending message: {}", why);
+    }
+
+    // Deleting the voice channel would take the event with it, so it is closed first
+    let event_id = session.read().await.event_id;
+    if let Some(event_id) = event_id {
+        let status = if is_started {
+            EventStatus::Completed
+        } else {
+            EventStatus::Canceled
+        };
+        set_event_status(ctx, event_id, status).await;
+    }
+
+    delete_temp_voice(ctx, session).await;
+    update_bot_status(ctx).await;
+}
+
+/// Ends the sessions which have been running for longer than the configured max_duration
+pub async fn end_overdue_sessions(ctx: &Context) {
+    let max_duration = match ctx
+        .config()
+        .await
+        .auto_end
+        .and_then(|auto_end| auto_end.max_duration)
+        .and_then(|max_duration| parse_duration(&max_duration))
+    {
+        Some(max_duration) => max_duration,
+        None => return,
+    };
+
+    let now = clock::now();
+    for session in ctx.sessions().await {
+        let time = session.read().await.time;
+        if now - time >= max_duration {
+            info!("Ending session which has been running for too long");
+            end_session(ctx, &session).await;
+        }
+    }
 }
 
 #[derive(Clone, Copy)]
@@ -52,36 +203,42 @@ impl InteractionHandler for EndHost {
 
 #[async_trait]
 impl CommandHandler for EndHost {
-    async fn invoke(&self, ctx: Context, interaction: ApplicationCommandInteraction) {
-        if !ctx.is_session_present().await {
-            interaction_respond_with_private_message(
-                &ctx,
-                &Interaction::ApplicationCommand(interaction),
-                "No session currently running!",
-            )
-            .await;
-            return;
-        }
+    async fn invoke(&self, ctx: Context, interaction: ApplicationCommandInteraction) -> Result<()> {
+        let session = match ctx.session(interaction.channel_id).await {
+            Some(session) => session,
+            None => {
+                let content = no_session_message(&ctx).await;
+                interaction_respond_with_private_message(
+                    &ctx,
+                    &Interaction::ApplicationCommand(interaction),
+                    &content,
+                )
+                .await;
+                return Ok(());
+            }
+        };
 
         let user_id = interaction.user.id;
-        if !can_cancel_session(&ctx, user_id).await {
+        let member = interaction.member.as_ref();
+        if !can_cancel_session(&ctx, &*session.read().await, user_id, member).await {
             interaction_respond_with_private_message(
                 &ctx,
                 &Interaction::ApplicationCommand(interaction),
                 "You don't have permissions to cancel this session!",
             )
             .await;
-            return;
+            return Ok(());
         }
 
-        let action = if ctx.is_session_started().await {
+        let action = if session.read().await.is_started() {
             "end"
         } else {
             "cancel"
         };
 
-        interaction
-            .create_interaction_response(&ctx.http, |response| {
+        ctx.discord()
+            .await
+            .create_interaction_response(interaction.id, &interaction.token, |response| {
                 response
                     .kind(InteractionResponseType::ChannelMessageWithSource)
                     .interaction_response_data(|message| {
@@ -91,8 +248,9 @@ impl CommandHandler for EndHost {
                             .flags(InteractionApplicationCommandCallbackDataFlags::EPHEMERAL)
                     })
             })
-            .await
-            .unwrap_or_else(|why| warn!("Error responding to interaction: {}", why));
+            .await?;
+
+        Ok(())
     }
 
     fn create_command(
@@ -101,7 +259,7 @@ impl CommandHandler for EndHost {
     ) -> &mut serenity::builder::CreateApplicationCommand {
         command
             .name(self.name())
-            .description("Ends/Cancels the current session")
+            .description("Ends/Cancels this channel's session")
     }
 }
 
@@ -119,86 +277,48 @@ impl InteractionHandler for ButtonYes {
 
 #[async_trait]
 impl MessageHandler for ButtonYes {
-    async fn invoke(&self, ctx: Context, interaction: MessageComponentInteraction) {
-        if !ctx.is_session_present().await {
+    async fn invoke(&self, ctx: Context, interaction: MessageComponentInteraction) -> Result<()> {
+        let session = match ctx.session(interaction.channel_id).await {
+            Some(session) => session,
+            None => {
+                interaction_respond_with_private_message(
+                    &ctx,
+                    &Interaction::MessageComponent(interaction),
+                    "No session currently running!",
+                )
+                .await;
+                return Ok(());
+            }
+        };
+
+        let is_started = session.read().await.is_started();
+        let action = if is_started { "ended" } else { "cancelled" };
+        let (user_id, member) = (interaction.user.id, interaction.member.as_ref());
+        if !can_cancel_session(&ctx, &*session.read().await, user_id, member).await {
             interaction_respond_with_private_message(
                 &ctx,
                 &Interaction::MessageComponent(interaction),
-                "No session currently running!",
+                format!("You don't have permission to {} this session!", action).as_str(),
             )
             .await;
-            return;
+            return Ok(());
         }
 
-        let action = if ctx.is_session_started().await {
-            "ended"
-        } else {
-            "cancelled"
-        };
-        if !can_cancel_session(&ctx, interaction.user.id).await {
+        if !stop_session(&ctx, &session).await {
             interaction_respond_with_private_message(
                 &ctx,
                 &Interaction::MessageComponent(interaction),
-                format!("You don't have permission to {} this session!", action).as_str(),
+                "No session currently running!",
             )
             .await;
-            return;
+            return Ok(());
         }
 
-        let message_id = ctx.session().await.read().await.message_id;
-        let game = ctx.session().await.read().await.game.clone();
-        // UNWRAP SAFETY: You can not host a game which has a ChannelId set to None
-        let channel_id = game.channel_id.unwrap();
-
-        if let Ok(message) = ctx
-            .http
-            .get_message(channel_id.0, message_id.0)
+        // Wrapping up takes many requests, which could take longer than an interaction may wait
+        let response = ctx
+            .discord()
             .await
-            .as_mut()
-        {
-            message
-                .edit(&ctx, |message| {
-                    message.components(|components| components.set_action_rows(vec![]))
-                })
-                .await
-                .unwrap_or_else(|why| warn!("Error editing message: {}", why));
-
-            message
-                .unpin(&ctx)
-                .await
-                .unwrap_or_else(|why| warn!("Error unpinning message: {}", why));
-        }
-
-        let content = if !ctx.is_session_started().await {
-            let user_pings =
-                users_with_state(&ctx.session().await.read().await.users, UserState::Will);
-            if user_pings.1 == 0 {
-                "".to_string()
-            } else {
-                user_pings.0 + ": "
-            }
-        } else {
-            "".to_string()
-        };
-
-        if let Err(why) = channel_id
-            .send_message(&ctx.http, |message| {
-                message.content(format!(
-                    "{}{} Session has been {}!",
-                    content, game.name, action
-                ))
-            })
-            .await
-        {
-            warn!("Error sending message: {}", why);
-        }
-
-        ctx.session().await.write().await.handle.abort();
-        ctx.data.write().await.remove::<Session>();
-        update_bot_status(&ctx).await;
-
-        interaction
-            .create_interaction_response(&ctx.http, |response| {
+            .create_interaction_response(interaction.id, &interaction.token, |response| {
                 response
                     .kind(InteractionResponseType::UpdateMessage)
                     .interaction_response_data(|message| {
@@ -207,8 +327,11 @@ impl MessageHandler for ButtonYes {
                             .components(|components| components.set_action_rows(vec![]))
                     })
             })
-            .await
-            .unwrap_or_else(|why| warn!("Error responding to interaction: {}", why));
+            .await;
+        wrap_up_session(&ctx, &session).await;
+        response?;
+
+        Ok(())
     }
 }
 
@@ -220,15 +343,16 @@ impl InteractionHandler for ButtonNo {
 
 #[async_trait]
 impl MessageHandler for ButtonNo {
-    async fn invoke(&self, ctx: Context, interaction: MessageComponentInteraction) {
-        let action = if ctx.is_session_started().await {
+    async fn invoke(&self, ctx: Context, interaction: MessageComponentInteraction) -> Result<()> {
+        let action = if ctx.is_session_started(interaction.channel_id).await {
             "ended"
         } else {
             "cancelled"
         };
 
-        interaction
-            .create_interaction_response(&ctx.http, |response| {
+        ctx.discord()
+            .await
+            .create_interaction_response(interaction.id, &interaction.token, |response| {
                 response
                     .kind(InteractionResponseType::UpdateMessage)
                     .interaction_response_data(|message| {
@@ -237,7 +361,8 @@ impl MessageHandler for ButtonNo {
                             .components(|f| f.set_action_rows(vec![]))
                     })
             })
-            .await
-            .unwrap_or_else(|why| warn!("Error responding to interaction: {}", why));
+            .await?;
+
+        Ok(())
     }
 }
@@ -0,0 +1,460 @@
+use crate::{
+    announcement::schedule_announcement_update,
+    commands::{
+        hostgame::{is_on_probation, resolve_session_time, start_session, SessionRequest},
+        prelude::*,
+    },
+    config::Capability,
+    config::Game,
+    context_ext::ContextExt,
+    interaction_handler::{is_allowed, CommandHandler, InteractionHandler, MessageHandler},
+    poll::{GameVote, Polls},
+    session::UserState,
+};
+
+use serenity::{
+    async_trait,
+    builder::{CreateActionRow, CreateSelectMenuOption},
+    client::Context,
+    model::interactions::{
+        application_command::{
+            ApplicationCommandInteraction, ApplicationCommandInteractionDataOptionValue,
+            ApplicationCommandOptionType,
+        },
+        message_component::{ButtonStyle, MessageComponentInteraction},
+    },
+};
+
+// Select menus can't have more options than this
+const MAX_GAMES: usize = 25;
+
+// The games a session can be hosted for
+fn votable_games(games: &[Game]) -> Vec<&Game> {
+    games
+        .iter()
+        .filter(|game| game.channel_id.is_some())
+        .take(MAX_GAMES)
+        .collect()
+}
+
+fn game_vote_content(games: &[Game], game_vote: &GameVote) -> String {
+    let mut content =
+        "What should we play? Pick a game, you can change your vote until the host closes it!\n"
+            .to_string();
+
+    for game in votable_games(games) {
+        content += &format!(
+            "\n{}: {} vote(s)",
+            game.name,
+            game_vote.vote_count(&game.name)
+        );
+    }
+
+    if !game_vote.description.is_empty() {
+        content += &format!("\n\nDescription: {}", game_vote.description);
+    }
+
+    content
+}
+
+fn get_action_rows(games: &[Game], game_vote: &GameVote) -> Vec<CreateActionRow> {
+    let option_vec = votable_games(games)
+        .into_iter()
+        .map(|game| {
+            // The name stays the same when the config's games are reordered, unlike an index
+            CreateSelectMenuOption::default()
+                .label(&game.name)
+                .description(format!("{} vote(s)", game_vote.vote_count(&game.name)))
+                .value(&game.name)
+                .clone()
+        })
+        .collect();
+
+    let menu = CreateActionRow::default()
+        .create_select_menu(|menu| {
+            menu.custom_id("gamevote-dropdown")
+                .placeholder("Pick a game")
+                .options(|options| options.set_options(option_vec))
+        })
+        .clone();
+
+    let buttons = CreateActionRow::default()
+        .create_button(|button| {
+            button
+                .custom_id("gamevote-close")
+                .label("Close vote")
+                .style(ButtonStyle::Danger)
+        })
+        .clone();
+
+    vec![menu, buttons]
+}
+
+#[derive(Clone, Copy)]
+pub struct GameVoteCommand;
+
+impl InteractionHandler for GameVoteCommand {
+    fn name(&self) -> &'static str {
+        "gamevote"
+    }
+
+    fn capability(&self) -> Option<Capability> {
+        Some(Capability::Host)
+    }
+}
+
+#[async_trait]
+impl CommandHandler for GameVoteCommand {
+    async fn invoke(&self, ctx: Context, interaction: ApplicationCommandInteraction) -> Result<()> {
+        let mut time = None;
+        let mut description = String::new();
+
+        for option in &interaction.data.options {
+            match (option.name.as_str(), option.resolved.as_ref()) {
+                ("time", Some(ApplicationCommandInteractionDataOptionValue::String(s))) => {
+                    time = Some(s.clone())
+                }
+                ("description", Some(ApplicationCommandInteractionDataOptionValue::String(s))) => {
+                    description = s.clone()
+                }
+                _ => {}
+            }
+        }
+
+        // The time is only resolved once the vote closes, but typos should be caught now
+        if let Err(why) = resolve_session_time(&ctx, interaction.user.id, time.clone()).await {
+            interaction_respond_with_private_message(
+                &ctx,
+                &Interaction::ApplicationCommand(interaction),
+                &why.to_string(),
+            )
+            .await;
+            return Ok(());
+        }
+
+        let games = ctx.config().await.games;
+        if votable_games(&games).len() < 2 {
+            interaction_respond_with_private_message(
+                &ctx,
+                &Interaction::ApplicationCommand(interaction),
+                "There need to be at least two games with a channel to vote on!",
+            )
+            .await;
+            return Ok(());
+        }
+
+        let mut game_vote = GameVote {
+            channel_id: interaction.channel_id,
+            message_id: MessageId::default(),
+            host: interaction.user.id,
+            time,
+            description,
+            votes: Default::default(),
+        };
+
+        let message = ctx
+            .discord()
+            .await
+            .send_message(interaction.channel_id, |message| {
+                message
+                    .content(game_vote_content(&games, &game_vote))
+                    .components(|components| {
+                        components.set_action_rows(get_action_rows(&games, &game_vote))
+                    })
+            })
+            .await;
+
+        let content = match message {
+            Ok(message_id) => {
+                game_vote.message_id = message_id;
+                ctx.data
+                    .read()
+                    .await
+                    .get::<Polls>()
+                    .expect("Error reading polls from TypeMap")
+                    .write()
+                    .await
+                    .open_game_vote(game_vote);
+                "Vote posted! Close it once everyone has voted"
+            }
+            Err(why) => {
+                warn!("Error sending game vote message: {}", why);
+                "The vote could not be posted!"
+            }
+        };
+
+        interaction_respond_with_private_message(
+            &ctx,
+            &Interaction::ApplicationCommand(interaction),
+            content,
+        )
+        .await;
+
+        Ok(())
+    }
+
+    fn create_command(
+        self,
+        command: &mut serenity::builder::CreateApplicationCommand,
+    ) -> &mut serenity::builder::CreateApplicationCommand {
+        command
+            .name(self.name())
+            .description("Lets everyone vote on which game to host a session for")
+            .create_option(|option| {
+                option
+                    .kind(ApplicationCommandOptionType::String)
+                    .name("time")
+                    .description("When to host the session, e.g. 21:00, friday 20:30, in 45m")
+            })
+            .create_option(|option| {
+                option
+                    .kind(ApplicationCommandOptionType::String)
+                    .name("description")
+                    .description("Sets the session description")
+            })
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct MenuHandler;
+
+impl InteractionHandler for MenuHandler {
+    fn name(&self) -> &'static str {
+        "gamevote-dropdown"
+    }
+}
+
+#[async_trait]
+impl MessageHandler for MenuHandler {
+    async fn invoke(&self, ctx: Context, interaction: MessageComponentInteraction) -> Result<()> {
+        let games = ctx.config().await.games;
+
+        // The game may have been removed from the config, or lost its channel, since the vote was posted
+        let game = interaction.data.values.first().and_then(|value| {
+            votable_games(&games)
+                .into_iter()
+                .find(|game| game.name == *value)
+                .map(|game| game.name.clone())
+        });
+        let game = match game {
+            Some(game) => game,
+            None => {
+                interaction_respond_with_private_message(
+                    &ctx,
+                    &Interaction::MessageComponent(interaction),
+                    "That game doesn't exist anymore!",
+                )
+                .await;
+                return Ok(());
+            }
+        };
+
+        let game_vote = ctx
+            .data
+            .read()
+            .await
+            .get::<Polls>()
+            .expect("Error reading polls from TypeMap")
+            .write()
+            .await
+            .vote_for_game(interaction.message.id, interaction.user.id, game);
+
+        let game_vote = match game_vote {
+            Some(game_vote) => game_vote,
+            None => {
+                interaction_respond_with_private_message(
+                    &ctx,
+                    &Interaction::MessageComponent(interaction),
+                    "This vote is already closed!",
+                )
+                .await;
+                return Ok(());
+            }
+        };
+
+        ctx.discord()
+            .await
+            .create_interaction_response(interaction.id, &interaction.token, |response| {
+                response
+                    .kind(InteractionResponseType::UpdateMessage)
+                    .interaction_response_data(|message| {
+                        message
+                            .content(game_vote_content(&games, &game_vote))
+                            .components(|components| {
+                                components.set_action_rows(get_action_rows(&games, &game_vote))
+                            })
+                    })
+            })
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct ButtonClose;
+
+impl InteractionHandler for ButtonClose {
+    fn name(&self) -> &'static str {
+        "gamevote-close"
+    }
+}
+
+// Starts a session of the winning game, with everyone who voted for it saying Yes.
+// Returns the game along with its channel, or why the vote can't be closed yet
+async fn host_winner(ctx: &Context, game_vote: &GameVote) -> Result<(Game, ChannelId), String> {
+    let games = ctx.config().await.games;
+    let names = votable_games(&games)
+        .into_iter()
+        .map(|game| game.name.as_str())
+        .collect::<Vec<_>>();
+
+    let winner = match game_vote.winner(&names) {
+        Some(winner) => winner,
+        None => return Err("Nobody has voted yet!".to_string()),
+    };
+    // UNWRAP SAFETY: The winner is one of the votable games, which all have a channel
+    let game = games
+        .iter()
+        .find(|game| game.name == winner)
+        .unwrap()
+        .clone();
+    let channel_id = game.channel_id.unwrap();
+
+    if ctx.is_session_present(channel_id).await {
+        return Err(format!(
+            "{} won, but there is already a session running in <#{}>! End it first",
+            game.name, channel_id
+        ));
+    }
+
+    let time = resolve_session_time(ctx, game_vote.host, game_vote.time.clone())
+        .await
+        .map_err(|why| why.to_string())?
+        .0;
+
+    let request = SessionRequest {
+        host: game_vote.host,
+        time,
+        description: game_vote.description.clone(),
+        max_players: None,
+        temp_voice: false,
+        scheduled_event: false,
+    };
+    if let Err(why) = start_session(ctx, channel_id, request).await {
+        warn!("Error starting session for game vote: {}", why);
+        return Err(why.user_message());
+    }
+
+    if let Some(session) = ctx.session(channel_id).await {
+        for user_id in game_vote.voters(&game.name) {
+            let on_probation = is_on_probation(ctx, user_id).await;
+            session
+                .write()
+                .await
+                .respond(user_id, UserState::Will, on_probation);
+        }
+
+        ctx.save_sessions().await;
+        schedule_announcement_update(ctx, session).await;
+    }
+
+    Ok((game, channel_id))
+}
+
+#[async_trait]
+impl MessageHandler for ButtonClose {
+    async fn invoke(&self, ctx: Context, interaction: MessageComponentInteraction) -> Result<()> {
+        let polls = ctx
+            .data
+            .read()
+            .await
+            .get::<Polls>()
+            .expect("Error reading polls from TypeMap")
+            .clone();
+
+        let game_vote = match polls.read().await.game_vote(interaction.message.id) {
+            Some(game_vote) => game_vote,
+            None => {
+                interaction_respond_with_private_message(
+                    &ctx,
+                    &Interaction::MessageComponent(interaction),
+                    "This vote is already closed!",
+                )
+                .await;
+                return Ok(());
+            }
+        };
+
+        let user_id = interaction.user.id;
+        let member = interaction.member.as_ref();
+        if user_id != game_vote.host
+            && !is_allowed(&ctx, Some(Capability::EndAnySession), user_id, member).await
+        {
+            interaction_respond_with_private_message(
+                &ctx,
+                &Interaction::MessageComponent(interaction),
+                "Only the host can close this vote!",
+            )
+            .await;
+            return Ok(());
+        }
+
+        // Taking the vote out first means a double click can't host the winner twice
+        let game_vote = match polls.write().await.close_game_vote(game_vote.message_id) {
+            Some(game_vote) => game_vote,
+            None => {
+                interaction_respond_with_private_message(
+                    &ctx,
+                    &Interaction::MessageComponent(interaction),
+                    "This vote is already closed!",
+                )
+                .await;
+                return Ok(());
+            }
+        };
+
+        let (game, channel_id) = match host_winner(&ctx, &game_vote).await {
+            Ok(winner) => winner,
+            Err(why) => {
+                polls.write().await.open_game_vote(game_vote);
+                interaction_respond_with_private_message(
+                    &ctx,
+                    &Interaction::MessageComponent(interaction),
+                    &why,
+                )
+                .await;
+                return Ok(());
+            }
+        };
+        let games = ctx.config().await.games;
+        let mut content = format!(
+            "Voting has closed! {} won with {} vote(s), the session has been planned in <#{}>\n",
+            game.name,
+            game_vote.vote_count(&game.name),
+            channel_id
+        );
+        for game in votable_games(&games) {
+            content += &format!(
+                "\n{}: {} vote(s)",
+                game.name,
+                game_vote.vote_count(&game.name)
+            );
+        }
+
+        ctx.discord()
+            .await
+            .create_interaction_response(interaction.id, &interaction.token, |response| {
+                response
+                    .kind(InteractionResponseType::UpdateMessage)
+                    .interaction_response_data(|message| {
+                        message
+                            .content(content)
+                            .components(|components| components.set_action_rows(vec![]))
+                    })
+            })
+            .await?;
+
+        Ok(())
+    }
+}
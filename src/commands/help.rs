@@ -62,12 +62,13 @@ impl InteractionHandler for Help {
 
 #[async_trait]
 impl CommandHandler for Help {
-    async fn invoke(&self, ctx: Context, interaction: ApplicationCommandInteraction) {
+    async fn invoke(&self, ctx: Context, interaction: ApplicationCommandInteraction) -> Result<()> {
         let embed = ctx.config().await.default_help.to_discord_embed();
         let action_row = get_action_row(&ctx).await;
 
-        interaction
-            .create_interaction_response(&ctx.http, |response| {
+        ctx.discord()
+            .await
+            .create_interaction_response(interaction.id, &interaction.token, |response| {
                 response
                     .kind(InteractionResponseType::ChannelMessageWithSource)
                     .interaction_response_data(|message| {
@@ -77,8 +78,9 @@ impl CommandHandler for Help {
                             .flags(InteractionApplicationCommandCallbackDataFlags::EPHEMERAL)
                     })
             })
-            .await
-            .unwrap_or_else(|why| warn!("Error responding to interaction: {}", why));
+            .await?;
+
+        Ok(())
     }
 
     fn create_command(
@@ -100,22 +102,21 @@ impl InteractionHandler for MenuHandler {
 
 #[async_trait]
 impl MessageHandler for MenuHandler {
-    async fn invoke(&self, ctx: Context, interaction: MessageComponentInteraction) {
-        // The conversion should always be valid unless a request is forged via modifications
-        // This is due to the fact .values[0] will always be one set via HelpPage::get_option
-        let index = interaction.clone().data.values[0]
-            .parse::<usize>()
-            .expect("Error parsing help-page data to usize");
-
-        let embed = ctx.config().await.help[index].embed.to_discord_embed();
+    async fn invoke(&self, ctx: Context, interaction: MessageComponentInteraction) -> Result<()> {
+        // The values are the ones set via HelpPage::get_option
+        let help = ctx.config().await.help;
+        let index = selected_index(&interaction, help.len())?;
+        let embed = help[index].embed.to_discord_embed();
 
-        interaction
-            .create_interaction_response(&ctx.http, |response| {
+        ctx.discord()
+            .await
+            .create_interaction_response(interaction.id, &interaction.token, |response| {
                 response
                     .kind(InteractionResponseType::UpdateMessage)
                     .interaction_response_data(|message| message.add_embed(embed))
             })
-            .await
-            .unwrap_or_else(|why| warn!("Error responding to interaction: {}", why));
+            .await?;
+
+        Ok(())
     }
 }
@@ -1,325 +1,520 @@
-use std::sync::Arc;
+use std::{collections::HashMap, path::Path};
 
 use crate::{
+    announcement::{schedule_announcement_update, update_announcement},
+    attendance::sync_attendance,
+    clock,
+    config::Capability,
     context_ext::ContextExt,
+    discord::is_unknown,
+    error::{Error, Result},
+    guild_event::{create_event, set_event_status, EventStatus},
+    history::History,
     interaction_handler::{CommandHandler, InteractionHandler, MessageHandler},
-    session::{Session, UserState},
+    session::{Rsvp, SavedSession, Session, UserState, LEGACY_SESSION_PATH, SESSIONS_PATH},
+    store,
+    time_parse::{parse_duration, parse_session_time, TimeParseError},
+    timer::spawn_session_task,
+    voice::{create_temp_voice, delete_voice_channel},
 };
 
-use super::{
-    prelude::{interaction_respond_with_private_message, update_bot_status},
-    status::get_status_embed,
-};
-use chrono::{DateTime, Local, NaiveDateTime, NaiveTime, TimeZone};
+use super::prelude::{interaction_respond_with_private_message, update_bot_status};
+use chrono::{DateTime, NaiveTime, Utc};
+use chrono_tz::Tz;
 use serenity::{
     async_trait,
+    builder::CreateActionRow,
     client::Context,
     model::{
-        channel::Message,
-        id::{ChannelId, RoleId},
+        id::{ChannelId, MessageId, RoleId, UserId},
         interactions::{
             application_command::{
                 ApplicationCommandInteraction, ApplicationCommandInteractionDataOptionValue,
                 ApplicationCommandOptionType,
             },
             message_component::{ButtonStyle, MessageComponentInteraction},
-            Interaction, InteractionResponseType,
+            Interaction, InteractionApplicationCommandCallbackDataFlags, InteractionResponseType,
         },
     },
-    prelude::RwLock,
+    prelude::TypeMapKey,
 };
-use tracing::warn;
+use tracing::{info, warn};
 
 #[derive(Clone, Copy)]
 pub struct HostGame;
 
-async fn ping_all_not_in_vc(ctx: &Context, channel_id: ChannelId) {
-    let user_map = ctx.session().await.read().await.users.clone();
-    let members = ctx
-        .config()
-        .await
-        .vc_channel
-        .to_channel(&ctx.http)
-        .await
-        .expect("Could not convert to Channel")
-        .guild()
-        .expect("Could not convert to GuildChannel")
-        .members(&ctx.cache)
-        .await
-        .expect("Could not retrieve Member list");
-
-    let pings = user_map
-        .iter()
-        .filter(|(u, s)| **s == UserState::Will && !members.iter().any(|m| m.user.id == **u))
-        .fold(String::new(), |lhs, (rhs, _)| {
-            lhs + format!("<@{}> ", rhs).as_str()
-        });
-
-    if pings.is_empty() {
-        return;
-    }
-
-    let content = format!("{}you're late, get in the VC!", pings);
-    if let Err(why) = channel_id
-        .send_message(&ctx.http, |message| message.content(content))
-        .await
-    {
-        warn!("Error sending message to text channel: {}", why);
-    }
+/// Everything needed to post a new session, besides the channel it is posted in
+#[derive(Clone)]
+pub struct SessionRequest {
+    pub host: UserId,
+    pub time: DateTime<Utc>,
+    pub description: String,
+    // Overrides the game's max_players when set
+    pub max_players: Option<usize>,
+    // Whether to create a voice channel just for this session
+    pub temp_voice: bool,
+    // Whether to mirror the session as a guild scheduled event
+    pub scheduled_event: bool,
 }
 
-async fn start_session(
+/// Posts the announcement for a new session in the game channel `channel_id` and starts tracking it
+pub async fn start_session(
     ctx: &Context,
-    interaction: &ApplicationCommandInteraction,
-    time: &str,
-    description: &str,
-) -> bool {
-    let channel_id = interaction.channel_id;
-    let guild_id = interaction.guild_id.unwrap_or_default();
-
-    let session_time =
-        NaiveTime::parse_from_str(time, "%H:%M").expect("Error parsing default time to string");
-    let now = Local::now();
-    let today = Local::today();
-    let session_time = Local
-        .from_local_datetime(&NaiveDateTime::new(today.naive_local(), session_time))
-        .earliest()
-        .expect("Error parsing time to DateTime");
-
-    let session_time = if (session_time - now) < chrono::Duration::zero() {
-        session_time
-            .date()
-            .succ()
-            .and_time(session_time.time())
-            .unwrap()
+    channel_id: ChannelId,
+    request: SessionRequest,
+) -> Result<()> {
+    let config = ctx.config().await;
+    let game = config
+        .games
+        .iter()
+        .find(|g| g.channel_id == Some(channel_id))
+        .ok_or(Error::NotGameChannel(channel_id))?
+        .clone();
+
+    let temp_voice_channel = if request.temp_voice {
+        create_temp_voice(ctx, &config, &game).await
     } else {
-        session_time
+        None
     };
 
-    let ctx2 = ctx.clone();
-    let handle = tokio::task::spawn(async move {
-        let ctx = ctx2.clone();
-        let ten_minutes_before =
-            session_time.signed_duration_since(now) - chrono::Duration::minutes(10);
+    let message_id = match send_session_message(
+        ctx,
+        channel_id,
+        request.time,
+        &request.description,
+        game.role_id,
+        temp_voice_channel,
+    )
+    .await
+    {
+        Ok(message_id) => message_id,
+        Err(why) => {
+            // Without an announcement nobody would know about the channel
+            if let Some(temp_voice_channel) = temp_voice_channel {
+                delete_voice_channel(ctx, temp_voice_channel).await;
+            }
+            return Err(why);
+        }
+    };
 
-        tokio::time::sleep(
-            ten_minutes_before
-                .to_std()
-                .unwrap_or_else(|_| std::time::Duration::from_secs(60)),
+    let handle = spawn_session_task(
+        ctx,
+        channel_id,
+        config.guild_id,
+        request.time,
+        config.reminders_for(&game),
+    );
+
+    let max_players = request.max_players.or(game.max_players);
+    let mut session = Session::new(
+        game,
+        handle,
+        request.time,
+        request.description,
+        message_id,
+        request.host,
+        max_players,
+    );
+    session.temp_voice_channel = temp_voice_channel;
+    if request.scheduled_event {
+        session.event_id = create_event(
+            ctx,
+            &format!("{} session", session.game.name),
+            &session.description,
+            session.time,
+            session.voice_channel(&config),
         )
         .await;
+    }
+    // Another session may have taken the channel while this one was being set up
+    if let Err(session) = ctx.insert_session(session).await {
+        discard_session(ctx, session).await;
+        return Err(Error::SessionRunning(channel_id));
+    }
+    ctx.save_sessions().await;
+    update_bot_status(ctx).await;
 
-        let game = ctx.session().await.read().await.game.clone();
-        let embed = get_status_embed(&ctx, guild_id).await;
+    if let Some(session) = ctx.session(channel_id).await {
+        update_announcement(ctx, &session).await;
+    }
 
-        channel_id
-            .send_message(&ctx.http, |message| {
-                message
-                    .set_embed(embed)
-                    .content(format!(
-                        "<@&{}> Session starting soon!",
-                        game.role_id.to_string()
-                    ))
-                    .allowed_mentions(|mentions| mentions.roles(vec![game.role_id]))
-            })
-            .await
-            .expect("Error sending message to channel");
+    Ok(())
+}
 
-        tokio::time::sleep(
-            session_time
-                .signed_duration_since(now)
-                .to_std()
-                .unwrap_or_default(),
-        )
-        .await;
+// Undoes everything start_session did for a session which never got to run
+async fn discard_session(ctx: &Context, session: Session) {
+    session.handle.abort();
 
-        let member_amount = ctx
-            .session()
-            .await
-            .read()
-            .await
-            .users
-            .iter()
-            .filter(|(_, s)| **s == UserState::Will)
-            .count();
-
-        let embed = get_status_embed(&ctx, guild_id).await;
-        let person_or_people = if member_amount == 1 {
-            "person"
-        } else {
-            "people"
-        };
+    let channel_id = session.channel_id();
+    if let Err(why) = ctx
+        .discord()
+        .await
+        .delete_message(channel_id, session.message_id)
+        .await
+    {
+        warn!("Error deleting discarded session message: {}", why);
+    }
+    if let Some(event_id) = session.event_id {
+        set_event_status(ctx, event_id, EventStatus::Canceled).await;
+    }
+    if let Some(temp_voice_channel) = session.temp_voice_channel {
+        delete_voice_channel(ctx, temp_voice_channel).await;
+    }
+}
 
-        channel_id
-            .send_message(&ctx.http, |message| {
-                message.set_embed(embed).content(format!(
-                    "{} Session has started! {} {} said Yes!",
-                    game.name, member_amount, person_or_people
-                ))
-            })
+/// Picks the sessions saved by a previous run back up, re-spawning their tasks and
+/// re-attaching the buttons to their announcement messages
+pub async fn restore_sessions(ctx: &Context) {
+    let saved_sessions = read_saved_sessions();
+    let config = ctx.config().await;
+
+    for saved in saved_sessions {
+        // UNWRAP SAFETY: You can not host a game which has a ChannelId set to None
+        let channel_id = saved.game.channel_id.unwrap();
+        if ctx.is_session_present(channel_id).await {
+            continue;
+        }
+
+        // Re-attaching the buttons fails when the message is gone, along with the session.
+        // Any other failure may go away on its own, so the session is kept without them
+        let edit = ctx
+            .discord()
             .await
-            .expect("Error sending message to channel");
+            .edit_message(channel_id, saved.message_id, |message| {
+                message.components(|components| components.set_action_rows(vec![get_action_row()]))
+            })
+            .await;
+        match edit {
+            Err(why) if is_unknown(&why) => {
+                warn!(
+                    "Dropping saved {} session, its message is gone: {}",
+                    saved.game.name, why
+                );
+                continue;
+            }
+            Err(why) => warn!(
+                "Error re-attaching the buttons of the saved {} session: {}",
+                saved.game.name, why
+            ),
+            Ok(()) => {}
+        }
 
-        update_bot_status(&ctx).await;
+        info!("Restored saved {} session", saved.game.name);
+        let handle = spawn_session_task(
+            ctx,
+            channel_id,
+            config.guild_id,
+            saved.time,
+            config.reminders_for(&saved.game),
+        );
+        if let Err(session) = ctx.insert_session(Session::from_saved(saved, handle)).await {
+            session.handle.abort();
+            continue;
+        }
 
-        tokio::time::sleep(std::time::Duration::from_secs(60 * 10)).await;
-        // ping users who said yes but not in VC
-        ping_all_not_in_vc(&ctx, channel_id).await;
-    });
+        // The countdown on the message went stale while the bot was offline,
+        // and people may have come and gone from the voice channel
+        if let Some(session) = ctx.session(channel_id).await {
+            sync_attendance(ctx, &session).await;
+            update_announcement(ctx, &session).await;
+        }
+    }
 
-    let game = match ctx
-        .config()
-        .await
-        .games
-        .iter()
-        .find(|g| g.channel_id == Some(channel_id))
-    {
-        Some(g) => g,
-        None => {
-            handle.abort();
+    // The sessions are saved in the new file now, so the old one would only restore them twice
+    ctx.save_sessions().await;
+    store::remove(Path::new(LEGACY_SESSION_PATH));
+}
+
+// Falls back to the session saved by a version of the bot which only ran one at a time
+fn read_saved_sessions() -> Vec<SavedSession> {
+    if let Some(saved_sessions) = store::read_from(Path::new(SESSIONS_PATH)) {
+        return saved_sessions;
+    }
 
-            return false;
+    match store::read_from::<SavedSession>(Path::new(LEGACY_SESSION_PATH)) {
+        Some(saved) => {
+            info!("Migrating the session saved in {}", LEGACY_SESSION_PATH);
+            vec![saved]
         }
+        None => vec![],
     }
-    .clone();
+}
 
-    let message = send_session_message(
-        ctx.clone(),
-        interaction,
-        session_time,
-        description,
-        game.role_id,
-    )
-    .await;
+fn get_action_row() -> CreateActionRow {
+    CreateActionRow::default()
+        .create_button(|button| {
+            button
+                .custom_id("button-yes")
+                .label("Yes")
+                .style(ButtonStyle::Success)
+        })
+        .create_button(|button| {
+            button
+                .custom_id("button-maybe")
+                .label("Maybe")
+                .style(ButtonStyle::Secondary)
+        })
+        .create_button(|button| {
+            button
+                .custom_id("button-no")
+                .label("No")
+                .style(ButtonStyle::Danger)
+        })
+        .create_button(|button| {
+            button
+                .custom_id("button-calendar")
+                .label("Add to calendar")
+                .style(ButtonStyle::Secondary)
+        })
+        .clone()
+}
 
-    ctx.data
-        .write()
-        .await
-        .insert::<Session>(Arc::new(RwLock::new(Session::new(
-            game,
-            handle,
-            session_time,
-            message.id,
-            interaction.user.id,
-        ))));
-    update_bot_status(ctx).await;
+/// The text of a session's announcement message, which is kept in sync when the session changes
+pub fn session_message_content(
+    role_id: RoleId,
+    time: DateTime<Utc>,
+    description: &str,
+    voice_channel: Option<ChannelId>,
+) -> String {
+    let mut content = format!(
+        "<@&{}> A session is planned!\nTime: <t:{}>\n",
+        role_id,
+        time.timestamp()
+    );
+
+    if let Some(voice_channel) = voice_channel {
+        content += &format!("Voice channel: <#{}>\n", voice_channel);
+    }
+
+    if !description.is_empty() {
+        content += &format!("Description: {}", description);
+    }
 
-    true
+    content
 }
 
 async fn send_session_message(
-    ctx: Context,
-    interaction: &ApplicationCommandInteraction,
-    time: DateTime<Local>,
+    ctx: &Context,
+    channel_id: ChannelId,
+    time: DateTime<Utc>,
     description: &str,
     role_id: RoleId,
-) -> Message {
-    let description = if description.is_empty() {
-        description.to_string()
-    } else {
-        format!("Description: {}", description)
+    voice_channel: Option<ChannelId>,
+) -> Result<MessageId> {
+    let discord = ctx.discord().await;
+    let message_id = discord
+        .send_message(channel_id, |message| {
+            message
+                .content(session_message_content(
+                    role_id,
+                    time,
+                    description,
+                    voice_channel,
+                ))
+                .allowed_mentions(|mentions| mentions.roles(vec![role_id]))
+                .components(|components| components.add_action_row(get_action_row()))
+        })
+        .await?;
+
+    // The session works fine without being pinned, it is only harder to find
+    if let Err(why) = discord.pin_message(channel_id, message_id).await {
+        warn!("Error pinning session message: {}", why);
+    }
+    Ok(message_id)
+}
+
+/// Resolves a time typed by `user_id`, which is read in their own timezone, or the default time
+/// in the server's timezone when no time was typed. Returns the time along with that timezone
+pub async fn resolve_session_time(
+    ctx: &Context,
+    user_id: UserId,
+    time: Option<String>,
+) -> Result<(DateTime<Utc>, Tz), TimeParseError> {
+    let config = ctx.config().await;
+    let (time, timezone) = match time {
+        Some(time) => (time, ctx.user_timezone(user_id).await),
+        None => (config.default_time.clone(), config.timezone),
     };
 
-    interaction
-        .create_interaction_response(&ctx.http, |response| {
-            response
-                .kind(InteractionResponseType::ChannelMessageWithSource)
-                .interaction_response_data(|message| {
-                    message
-                        .content(format!(
-                            "<@&{}> A session is planned!\nTime: <t:{}>\n{}",
-                            role_id,
-                            time.timestamp(),
-                            description
-                        ))
-                        .allowed_mentions(|mentions| mentions.roles(vec![role_id]))
-                        .components(|component| {
-                            component.create_action_row(|row| {
-                                row.create_button(|button| {
-                                    button
-                                        .custom_id("button-yes")
-                                        .label("Yes")
-                                        .style(ButtonStyle::Success)
-                                })
-                                .create_button(|button| {
-                                    button
-                                        .custom_id("button-maybe")
-                                        .label("Maybe")
-                                        .style(ButtonStyle::Secondary)
-                                })
-                                .create_button(|button| {
-                                    button
-                                        .custom_id("button-no")
-                                        .label("No")
-                                        .style(ButtonStyle::Danger)
-                                })
-                            })
-                        })
-                })
-        })
-        .await
-        .expect("Error responding to interaction");
+    let now = clock::now().with_timezone(&timezone);
+    let default_time = NaiveTime::parse_from_str(&config.default_time, "%H:%M").ok();
+    let session_time = parse_session_time(&time, now, default_time)?;
+    Ok((session_time.with_timezone(&Utc), timezone))
+}
 
-    let message = interaction
-        .get_interaction_response(&ctx.http)
-        .await
-        .expect("Error retrieving interaction response");
+// /hostgame invocations waiting for the host to confirm the resolved time
+pub struct PendingSessionMap;
 
-    message.pin(&ctx.http).await.expect("Error pinning message");
-    message
+impl TypeMapKey for PendingSessionMap {
+    type Value = HashMap<(ChannelId, UserId), SessionRequest>;
+}
+
+fn get_preview_action_row() -> CreateActionRow {
+    CreateActionRow::default()
+        .create_button(|button| {
+            button
+                .style(ButtonStyle::Success)
+                .label("Post")
+                .custom_id("hostgame-confirm")
+        })
+        .create_button(|button| {
+            button
+                .style(ButtonStyle::Danger)
+                .label("Cancel")
+                .custom_id("hostgame-cancel")
+        })
+        .clone()
 }
 
 impl InteractionHandler for HostGame {
     fn name(&self) -> &'static str {
         "hostgame"
     }
+
+    fn capability(&self) -> Option<Capability> {
+        Some(Capability::Host)
+    }
 }
 
 #[async_trait]
 impl CommandHandler for HostGame {
-    async fn invoke(&self, ctx: Context, interaction: ApplicationCommandInteraction) {
-        if ctx.is_session_present().await {
+    async fn invoke(&self, ctx: Context, interaction: ApplicationCommandInteraction) -> Result<()> {
+        if ctx.is_session_present(interaction.channel_id).await {
             interaction_respond_with_private_message(
                 &ctx,
                 &Interaction::ApplicationCommand(interaction),
-                "There is already a session running!",
+                "There is already a session running in this channel!",
             )
             .await;
-            return;
+            return Ok(());
         }
 
         let config = ctx.config().await;
-        let mut time = config.default_time;
+        if !config
+            .games
+            .iter()
+            .any(|g| g.channel_id == Some(interaction.channel_id))
+        {
+            interaction_respond_with_private_message(
+                &ctx,
+                &Interaction::ApplicationCommand(interaction),
+                "This is not a game channel!",
+            )
+            .await;
+            return Ok(());
+        }
+
+        let mut time = None;
         let mut description = String::new();
+        let mut max_players = None;
+        let mut temp_voice = false;
+        let mut scheduled_event = false;
 
         for option in &interaction.data.options {
             match option.name.as_ref() {
                 "time" => {
-                    if let ApplicationCommandInteractionDataOptionValue::String(s) =
-                        option.resolved.as_ref().unwrap()
+                    if let Some(ApplicationCommandInteractionDataOptionValue::String(s)) =
+                        option.resolved.as_ref()
                     {
-                        time = s.clone();
+                        time = Some(s.clone());
                     }
                 }
                 "description" => {
-                    if let ApplicationCommandInteractionDataOptionValue::String(s) =
-                        option.resolved.as_ref().unwrap()
+                    if let Some(ApplicationCommandInteractionDataOptionValue::String(s)) =
+                        option.resolved.as_ref()
                     {
                         description = s.clone();
                     }
                 }
+                "max_players" => {
+                    if let Some(ApplicationCommandInteractionDataOptionValue::Integer(i)) =
+                        option.resolved.as_ref()
+                    {
+                        max_players = Some(*i as usize);
+                    }
+                }
+                "voice" => {
+                    if let Some(ApplicationCommandInteractionDataOptionValue::Boolean(b)) =
+                        option.resolved.as_ref()
+                    {
+                        temp_voice = *b;
+                    }
+                }
+                "event" => {
+                    if let Some(ApplicationCommandInteractionDataOptionValue::Boolean(b)) =
+                        option.resolved.as_ref()
+                    {
+                        scheduled_event = *b;
+                    }
+                }
                 _ => {}
             }
         }
 
-        if !start_session(&ctx, &interaction, &time, &description).await {
+        if temp_voice && config.temp_voice.is_none() {
             interaction_respond_with_private_message(
                 &ctx,
                 &Interaction::ApplicationCommand(interaction),
-                "This is not a game channel!",
+                "Voice channels for sessions are not set up on this server!",
             )
             .await;
+            return Ok(());
         }
+
+        let (session_time, timezone) =
+            match resolve_session_time(&ctx, interaction.user.id, time).await {
+                Ok(resolved) => resolved,
+                Err(why) => {
+                    interaction_respond_with_private_message(
+                        &ctx,
+                        &Interaction::ApplicationCommand(interaction),
+                        &why.to_string(),
+                    )
+                    .await;
+                    return Ok(());
+                }
+            };
+
+        ctx.data
+            .write()
+            .await
+            .get_mut::<PendingSessionMap>()
+            .expect("Error reading pending sessions from TypeMap")
+            .insert(
+                (interaction.channel_id, interaction.user.id),
+                SessionRequest {
+                    host: interaction.user.id,
+                    time: session_time,
+                    description,
+                    max_players,
+                    temp_voice,
+                    scheduled_event,
+                },
+            );
+
+        ctx.discord()
+            .await
+            .create_interaction_response(interaction.id, &interaction.token, |response| {
+                response
+                    .kind(InteractionResponseType::ChannelMessageWithSource)
+                    .interaction_response_data(|message| {
+                        message
+                            .content(format!(
+                                "The session will be planned for <t:{0}:F> (<t:{0}:R>), \
+                                 read as {1} in {2}. Post it?",
+                                session_time.timestamp(),
+                                session_time.with_timezone(&timezone).format("%A %H:%M"),
+                                timezone.name()
+                            ))
+                            .components(|components| {
+                                components.add_action_row(get_preview_action_row())
+                            })
+                            .flags(InteractionApplicationCommandCallbackDataFlags::EPHEMERAL)
+                    })
+            })
+            .await?;
+
+        Ok(())
     }
 
     fn create_command(
@@ -333,7 +528,7 @@ impl CommandHandler for HostGame {
                 option
                     .kind(ApplicationCommandOptionType::String)
                     .name("time")
-                    .description("Time to host the session")
+                    .description("When to host the session, e.g. 21:00, friday 20:30, in 45m")
             })
             .create_option(|option| {
                 option
@@ -341,6 +536,229 @@ impl CommandHandler for HostGame {
                     .name("description")
                     .description("Sets the session description")
             })
+            .create_option(|option| {
+                option
+                    .kind(ApplicationCommandOptionType::Integer)
+                    .name("max_players")
+                    .description("Players allowed before people are put on the waitlist")
+                    .min_int_value(1)
+            })
+            .create_option(|option| {
+                option
+                    .kind(ApplicationCommandOptionType::Boolean)
+                    .name("voice")
+                    .description("Creates a voice channel just for this session")
+            })
+            .create_option(|option| {
+                option
+                    .kind(ApplicationCommandOptionType::Boolean)
+                    .name("event")
+                    .description("Also posts the session as a server event")
+            })
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct ButtonConfirm;
+
+#[derive(Clone, Copy)]
+pub struct ButtonCancel;
+
+impl InteractionHandler for ButtonConfirm {
+    fn name(&self) -> &'static str {
+        "hostgame-confirm"
+    }
+}
+
+#[async_trait]
+impl MessageHandler for ButtonConfirm {
+    async fn invoke(&self, ctx: Context, interaction: MessageComponentInteraction) -> Result<()> {
+        let channel_id = interaction.channel_id;
+        let pending = ctx
+            .data
+            .write()
+            .await
+            .get_mut::<PendingSessionMap>()
+            .expect("Error reading pending sessions from TypeMap")
+            .remove(&(channel_id, interaction.user.id));
+
+        let (content, pending) = match pending {
+            None => ("This preview has expired, use /hostgame again!", None),
+            Some(_) if ctx.is_session_present(channel_id).await => {
+                ("There is already a session running in this channel!", None)
+            }
+            Some(pending) if pending.time < clock::now() => {
+                ("That time has already passed, use /hostgame again!", None)
+            }
+            Some(pending) => ("Session posted!", Some(pending)),
+        };
+
+        ctx.discord()
+            .await
+            .create_interaction_response(interaction.id, &interaction.token, |response| {
+                response
+                    .kind(InteractionResponseType::UpdateMessage)
+                    .interaction_response_data(|message| {
+                        message
+                            .content(content)
+                            .components(|components| components.set_action_rows(vec![]))
+                    })
+            })
+            .await?;
+
+        if let Some(pending) = pending {
+            start_session(&ctx, channel_id, pending).await?;
+        }
+
+        Ok(())
+    }
+}
+
+impl InteractionHandler for ButtonCancel {
+    fn name(&self) -> &'static str {
+        "hostgame-cancel"
+    }
+}
+
+#[async_trait]
+impl MessageHandler for ButtonCancel {
+    async fn invoke(&self, ctx: Context, interaction: MessageComponentInteraction) -> Result<()> {
+        ctx.data
+            .write()
+            .await
+            .get_mut::<PendingSessionMap>()
+            .expect("Error reading pending sessions from TypeMap")
+            .remove(&(interaction.channel_id, interaction.user.id));
+
+        ctx.discord()
+            .await
+            .create_interaction_response(interaction.id, &interaction.token, |response| {
+                response
+                    .kind(InteractionResponseType::UpdateMessage)
+                    .interaction_response_data(|message| {
+                        message
+                            .content("Session has *not* been posted!")
+                            .components(|components| components.set_action_rows(vec![]))
+                    })
+            })
+            .await?;
+
+        Ok(())
+    }
+}
+
+// Lets the users who were moved off the waitlist know, in DMs or with a ping if their DMs are closed
+pub async fn notify_promoted(
+    ctx: &Context,
+    channel_id: ChannelId,
+    game: &str,
+    promoted: &[UserId],
+) {
+    for user_id in promoted {
+        let content = format!(
+            "A spot opened up in the {} session, you're in! See <#{}>",
+            game, channel_id
+        );
+
+        let dm = ctx
+            .discord()
+            .await
+            .send_dm(*user_id, |message| message.content(&content))
+            .await;
+
+        if let Err(why) = dm {
+            warn!("Error sending waitlist DM, pinging instead: {}", why);
+            if let Err(why) = ctx
+                .discord()
+                .await
+                .send_message(channel_id, |message| {
+                    message.content(format!(
+                        "<@{}> a spot opened up in the session, you're in!",
+                        user_id
+                    ))
+                })
+                .await
+            {
+                warn!("Error sending message to channel: {}", why);
+            }
+        }
+    }
+}
+
+// Whether the user said Yes without showing up more often than the no-show policy allows
+pub async fn is_on_probation(ctx: &Context, user_id: UserId) -> bool {
+    let policy = match ctx.config().await.no_show_policy {
+        Some(policy) => policy,
+        None => return false,
+    };
+    let period = match parse_duration(&policy.period) {
+        Some(period) => period,
+        None => return false,
+    };
+
+    let (showed_up, said_yes) = ctx
+        .data
+        .read()
+        .await
+        .get::<History>()
+        .expect("Error reading history from TypeMap")
+        .read()
+        .await
+        .reliability(user_id, Some(clock::now() - period));
+
+    said_yes - showed_up >= policy.max_no_shows
+}
+
+async fn respond_to_session(
+    ctx: &Context,
+    interaction: MessageComponentInteraction,
+    state: UserState,
+) {
+    let session = match ctx.session(interaction.channel_id).await {
+        Some(session) => session,
+        None => {
+            interaction_respond_with_private_message(
+                ctx,
+                &Interaction::MessageComponent(interaction),
+                "This session is no longer running!",
+            )
+            .await;
+            return;
+        }
+    };
+
+    let user_id = interaction.user.id;
+    let on_probation = state == UserState::Will && is_on_probation(ctx, user_id).await;
+    let (rsvp, promoted) = session.write().await.respond(user_id, state, on_probation);
+
+    let content = match (state, rsvp) {
+        (_, Some(Rsvp::Probation)) => format!(
+            "You've said Yes without showing up too often lately, <@{}>, so you're on the waitlist \
+             until the session starts. If there's room then, you're in!",
+            user_id
+        ),
+        (_, Some(Rsvp::Waitlisted(position))) => format!(
+            "The session is full! You're #{} on the waitlist, <@{}>, I'll let you know when a spot opens up",
+            position, user_id
+        ),
+        (UserState::Will, _) => format!("Thanks for saying yes, <@{}>", user_id),
+        (UserState::May, _) => format!("Thanks for saying maybe, <@{}>", user_id),
+        (UserState::Wont, _) => format!("Thanks for saying no, <@{}>", user_id),
+    };
+
+    interaction_respond_with_private_message(
+        ctx,
+        &Interaction::MessageComponent(interaction),
+        &content,
+    )
+    .await;
+
+    ctx.save_sessions().await;
+    schedule_announcement_update(ctx, session.clone()).await;
+
+    if !promoted.is_empty() {
+        let session = session.read().await;
+        notify_promoted(ctx, session.channel_id(), &session.game.name, &promoted).await;
     }
 }
 
@@ -361,21 +779,10 @@ impl InteractionHandler for ButtonYes {
 
 #[async_trait]
 impl MessageHandler for ButtonYes {
-    async fn invoke(&self, ctx: Context, interaction: MessageComponentInteraction) {
-        let user_id = interaction.user.id;
-        interaction_respond_with_private_message(
-            &ctx,
-            &Interaction::MessageComponent(interaction),
-            format!("Thanks for saying yes, <@{}>", user_id).as_str(),
-        )
-        .await;
+    async fn invoke(&self, ctx: Context, interaction: MessageComponentInteraction) -> Result<()> {
+        respond_to_session(&ctx, interaction, UserState::Will).await;
 
-        ctx.session()
-            .await
-            .write()
-            .await
-            .users
-            .insert(user_id, crate::session::UserState::Will);
+        Ok(())
     }
 }
 
@@ -388,21 +795,10 @@ impl InteractionHandler for ButtonMaybe {
 
 #[async_trait]
 impl MessageHandler for ButtonMaybe {
-    async fn invoke(&self, ctx: Context, interaction: MessageComponentInteraction) {
-        let user_id = interaction.user.id;
-        interaction_respond_with_private_message(
-            &ctx,
-            &Interaction::MessageComponent(interaction),
-            format!("Thanks for saying maybe, <@{}>", user_id).as_str(),
-        )
-        .await;
+    async fn invoke(&self, ctx: Context, interaction: MessageComponentInteraction) -> Result<()> {
+        respond_to_session(&ctx, interaction, UserState::May).await;
 
-        ctx.session()
-            .await
-            .write()
-            .await
-            .users
-            .insert(user_id, crate::session::UserState::May);
+        Ok(())
     }
 }
 
@@ -414,20 +810,9 @@ impl InteractionHandler for ButtonNo {
 
 #[async_trait]
 impl MessageHandler for ButtonNo {
-    async fn invoke(&self, ctx: Context, interaction: MessageComponentInteraction) {
-        let user_id = interaction.user.id;
-        interaction_respond_with_private_message(
-            &ctx,
-            &Interaction::MessageComponent(interaction),
-            format!("Thanks for saying no, <@{}>", user_id).as_str(),
-        )
-        .await;
+    async fn invoke(&self, ctx: Context, interaction: MessageComponentInteraction) -> Result<()> {
+        respond_to_session(&ctx, interaction, UserState::Wont).await;
 
-        ctx.session()
-            .await
-            .write()
-            .await
-            .users
-            .insert(user_id, crate::session::UserState::Wont);
+        Ok(())
     }
 }
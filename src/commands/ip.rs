@@ -1,4 +1,5 @@
 use crate::context_ext::ContextExt;
+use crate::error::Result;
 use crate::interaction_handler::{CommandHandler, InteractionHandler};
 
 use serenity::{
@@ -9,7 +10,6 @@ use serenity::{
         InteractionApplicationCommandCallbackDataFlags, InteractionResponseType,
     },
 };
-use tracing::log::warn;
 
 #[derive(Clone, Copy)]
 pub struct Ip;
@@ -22,10 +22,11 @@ impl InteractionHandler for Ip {
 
 #[async_trait]
 impl CommandHandler for Ip {
-    async fn invoke(&self, ctx: Context, interaction: ApplicationCommandInteraction) {
+    async fn invoke(&self, ctx: Context, interaction: ApplicationCommandInteraction) -> Result<()> {
         let embed = ctx.config().await.ip_embed.to_discord_embed();
-        let res = interaction
-            .create_interaction_response(&ctx.http, |response| {
+        ctx.discord()
+            .await
+            .create_interaction_response(interaction.id, &interaction.token, |response| {
                 response
                     .kind(InteractionResponseType::ChannelMessageWithSource)
                     .interaction_response_data(|message| {
@@ -34,11 +35,9 @@ impl CommandHandler for Ip {
                             .flags(InteractionApplicationCommandCallbackDataFlags::EPHEMERAL)
                     })
             })
-            .await;
+            .await?;
 
-        if let Err(why) = res {
-            warn!("Error responding to slash command: {}", why);
-        }
+        Ok(())
     }
 
     fn create_command(
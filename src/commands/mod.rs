@@ -1,9 +1,17 @@
 pub mod allroles;
+pub mod calendar;
+pub mod cohost;
 pub mod colors;
 pub mod endhost;
+pub mod gamevote;
 pub mod help;
 pub mod hostgame;
 pub mod ip;
+pub mod pollgame;
 pub mod prelude;
+pub mod reschedule;
 pub mod roles;
+pub mod skipschedule;
+pub mod stats;
 pub mod status;
+pub mod timezone;
@@ -0,0 +1,480 @@
+use crate::{
+    announcement::schedule_announcement_update,
+    clock,
+    commands::{
+        hostgame::{is_on_probation, resolve_session_time, start_session, SessionRequest},
+        prelude::*,
+    },
+    config::Capability,
+    context_ext::ContextExt,
+    interaction_handler::{CommandHandler, InteractionHandler, MessageHandler},
+    poll::{Poll, Polls},
+    session::UserState,
+    time_parse::parse_duration,
+};
+
+use chrono::{DateTime, Duration, Utc};
+use serenity::{
+    async_trait,
+    builder::{CreateActionRow, CreateSelectMenuOption},
+    client::Context,
+    model::interactions::{
+        application_command::{
+            ApplicationCommandInteraction, ApplicationCommandInteractionDataOptionValue,
+            ApplicationCommandOptionType,
+        },
+        message_component::MessageComponentInteraction,
+    },
+};
+
+// More options than this make the select menu hard to use
+const MAX_TIMES: usize = 10;
+
+// How long polls stay open when the host doesn't say, unless one of the times comes sooner
+const DEFAULT_CLOSES_IN: &str = "1d";
+
+// Polls close at least this many minutes before their first time, so people have time to plan
+const CLOSE_BEFORE_MINUTES: i64 = 15;
+
+fn poll_content(role_id: RoleId, poll: &Poll) -> String {
+    let mut content = format!(
+        "<@&{}> When should we play? Pick every time you can make it, \
+         voting closes <t:{}:R>\n",
+        role_id,
+        poll.closes_at.timestamp()
+    );
+
+    for (idx, time) in poll.times.iter().enumerate() {
+        content += &format!(
+            "\n<t:{}:F>: {} vote(s)",
+            time.timestamp(),
+            poll.vote_count(idx)
+        );
+    }
+
+    if !poll.description.is_empty() {
+        content += &format!("\n\nDescription: {}", poll.description);
+    }
+
+    content
+}
+
+fn get_action_row(poll: &Poll) -> CreateActionRow {
+    let option_vec = poll
+        .times
+        .iter()
+        .enumerate()
+        .map(|(idx, time)| {
+            CreateSelectMenuOption::default()
+                .label(time.with_timezone(&poll.timezone).format("%A %d %B %H:%M"))
+                .description(poll.timezone.name())
+                .value(idx)
+                .clone()
+        })
+        .collect();
+
+    CreateActionRow::default()
+        .create_select_menu(|menu| {
+            menu.custom_id("poll-dropdown")
+                .placeholder("Pick the times you can make")
+                .min_values(0)
+                .max_values(poll.times.len() as u64)
+                .options(|options| options.set_options(option_vec))
+        })
+        .clone()
+}
+
+#[derive(Clone, Copy)]
+pub struct PollGame;
+
+impl InteractionHandler for PollGame {
+    fn name(&self) -> &'static str {
+        "pollgame"
+    }
+
+    fn capability(&self) -> Option<Capability> {
+        Some(Capability::Host)
+    }
+}
+
+#[async_trait]
+impl CommandHandler for PollGame {
+    async fn invoke(&self, ctx: Context, interaction: ApplicationCommandInteraction) -> Result<()> {
+        let config = ctx.config().await;
+        let game = match config
+            .games
+            .iter()
+            .find(|game| game.channel_id == Some(interaction.channel_id))
+        {
+            Some(game) => game.clone(),
+            None => {
+                interaction_respond_with_private_message(
+                    &ctx,
+                    &Interaction::ApplicationCommand(interaction),
+                    "This is not a game channel!",
+                )
+                .await;
+                return Ok(());
+            }
+        };
+
+        let polls = ctx
+            .data
+            .read()
+            .await
+            .get::<Polls>()
+            .expect("Error reading polls from TypeMap")
+            .clone();
+        if polls.read().await.is_open_in(interaction.channel_id) {
+            interaction_respond_with_private_message(
+                &ctx,
+                &Interaction::ApplicationCommand(interaction),
+                "There is already a poll open in this channel!",
+            )
+            .await;
+            return Ok(());
+        }
+
+        let mut times = String::new();
+        let mut closes_in = DEFAULT_CLOSES_IN.to_string();
+        let mut description = String::new();
+        let mut max_players = None;
+
+        for option in &interaction.data.options {
+            match (option.name.as_str(), option.resolved.as_ref()) {
+                ("times", Some(ApplicationCommandInteractionDataOptionValue::String(s))) => {
+                    times = s.clone()
+                }
+                ("closes_in", Some(ApplicationCommandInteractionDataOptionValue::String(s))) => {
+                    closes_in = s.clone()
+                }
+                ("description", Some(ApplicationCommandInteractionDataOptionValue::String(s))) => {
+                    description = s.clone()
+                }
+                ("max_players", Some(ApplicationCommandInteractionDataOptionValue::Integer(i))) => {
+                    max_players = Some(*i as usize)
+                }
+                _ => {}
+            }
+        }
+
+        let mut candidates: Vec<DateTime<Utc>> = vec![];
+        let mut timezone = config.timezone;
+        for time in times
+            .split(',')
+            .map(str::trim)
+            .filter(|time| !time.is_empty())
+        {
+            match resolve_session_time(&ctx, interaction.user.id, Some(time.to_string())).await {
+                Ok((time, tz)) => {
+                    timezone = tz;
+                    if !candidates.contains(&time) {
+                        candidates.push(time);
+                    }
+                }
+                Err(why) => {
+                    interaction_respond_with_private_message(
+                        &ctx,
+                        &Interaction::ApplicationCommand(interaction),
+                        &format!("\"{}\": {}", time, why),
+                    )
+                    .await;
+                    return Ok(());
+                }
+            }
+        }
+        candidates.sort();
+
+        let latest_close = candidates
+            .first()
+            .map(|first| *first - Duration::minutes(CLOSE_BEFORE_MINUTES));
+        let error = if candidates.len() < 2 {
+            Some("Give at least two times to vote on, separated by commas!".to_string())
+        } else if candidates.len() > MAX_TIMES {
+            Some(format!("A poll can have at most {} times!", MAX_TIMES))
+        } else if latest_close.is_none_or(|latest_close| latest_close <= clock::now()) {
+            Some(format!(
+                "The first time is too soon to vote on, pick times at least {} minutes from now!",
+                CLOSE_BEFORE_MINUTES
+            ))
+        } else {
+            None
+        };
+        if let Some(error) = error {
+            interaction_respond_with_private_message(
+                &ctx,
+                &Interaction::ApplicationCommand(interaction),
+                &error,
+            )
+            .await;
+            return Ok(());
+        }
+
+        let closes_in = match parse_duration(&closes_in) {
+            Some(closes_in) if closes_in > Duration::zero() => closes_in,
+            _ => {
+                interaction_respond_with_private_message(
+                    &ctx,
+                    &Interaction::ApplicationCommand(interaction),
+                    "I couldn't understand when to close the poll! Try something like \"2h\" or \"1d\"",
+                )
+                .await;
+                return Ok(());
+            }
+        };
+
+        // UNWRAP SAFETY: There are at least two times at this point
+        let closes_at = (clock::now() + closes_in).min(latest_close.unwrap());
+
+        let mut poll = Poll {
+            channel_id: interaction.channel_id,
+            message_id: MessageId::default(),
+            host: interaction.user.id,
+            times: candidates,
+            timezone,
+            closes_at,
+            description,
+            max_players,
+            votes: Default::default(),
+        };
+
+        let message = ctx
+            .discord()
+            .await
+            .send_message(interaction.channel_id, |message| {
+                message
+                    .content(poll_content(game.role_id, &poll))
+                    .allowed_mentions(|mentions| mentions.roles(vec![game.role_id]))
+                    .components(|components| components.add_action_row(get_action_row(&poll)))
+            })
+            .await;
+
+        let content = match message {
+            Ok(message_id) => {
+                poll.message_id = message_id;
+                polls.write().await.open(poll);
+                "Poll posted!"
+            }
+            Err(why) => {
+                warn!("Error sending poll message: {}", why);
+                "The poll could not be posted!"
+            }
+        };
+
+        interaction_respond_with_private_message(
+            &ctx,
+            &Interaction::ApplicationCommand(interaction),
+            content,
+        )
+        .await;
+
+        Ok(())
+    }
+
+    fn create_command(
+        self,
+        command: &mut serenity::builder::CreateApplicationCommand,
+    ) -> &mut serenity::builder::CreateApplicationCommand {
+        command
+            .name(self.name())
+            .description("Lets everyone vote on when to host a session")
+            .create_option(|option| {
+                option
+                    .kind(ApplicationCommandOptionType::String)
+                    .name("times")
+                    .description("The times to vote on, separated by commas, e.g. friday 20:00, saturday 18:00")
+                    .required(true)
+            })
+            .create_option(|option| {
+                option
+                    .kind(ApplicationCommandOptionType::String)
+                    .name("closes_in")
+                    .description("How long voting stays open, e.g. 2h. A day when left out")
+            })
+            .create_option(|option| {
+                option
+                    .kind(ApplicationCommandOptionType::String)
+                    .name("description")
+                    .description("Sets the session description")
+            })
+            .create_option(|option| {
+                option
+                    .kind(ApplicationCommandOptionType::Integer)
+                    .name("max_players")
+                    .description("Players allowed before people are put on the waitlist")
+                    .min_int_value(1)
+            })
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct MenuHandler;
+
+impl InteractionHandler for MenuHandler {
+    fn name(&self) -> &'static str {
+        "poll-dropdown"
+    }
+}
+
+#[async_trait]
+impl MessageHandler for MenuHandler {
+    async fn invoke(&self, ctx: Context, interaction: MessageComponentInteraction) -> Result<()> {
+        let polls = ctx
+            .data
+            .read()
+            .await
+            .get::<Polls>()
+            .expect("Error reading polls from TypeMap")
+            .clone();
+
+        // The menu offers each of the poll's times once, so any other value was forged
+        let times = polls
+            .read()
+            .await
+            .poll(interaction.message.id)
+            .map(|poll| poll.times.len());
+        let mut votes = vec![];
+        for value in &interaction.data.values {
+            match value.parse::<usize>() {
+                Ok(index) if times.is_none_or(|len| index < len) && !votes.contains(&index) => {
+                    votes.push(index)
+                }
+                _ => return Err(Error::InvalidInteraction("Poll vote out of range")),
+            }
+        }
+
+        let poll = polls
+            .write()
+            .await
+            .vote(interaction.message.id, interaction.user.id, votes);
+
+        let role_id = ctx
+            .config()
+            .await
+            .games
+            .iter()
+            .find(|game| game.channel_id == Some(interaction.channel_id))
+            .map(|game| game.role_id);
+
+        let (poll, role_id) = match (poll, role_id) {
+            (Some(poll), Some(role_id)) => (poll, role_id),
+            _ => {
+                interaction_respond_with_private_message(
+                    &ctx,
+                    &Interaction::MessageComponent(interaction),
+                    "This poll is already closed!",
+                )
+                .await;
+                return Ok(());
+            }
+        };
+
+        ctx.discord()
+            .await
+            .create_interaction_response(interaction.id, &interaction.token, |response| {
+                response
+                    .kind(InteractionResponseType::UpdateMessage)
+                    .interaction_response_data(|message| {
+                        message
+                            .content(poll_content(role_id, &poll))
+                            .components(|components| {
+                                components.add_action_row(get_action_row(&poll))
+                            })
+                    })
+            })
+            .await?;
+
+        Ok(())
+    }
+}
+
+// Starts the session at the winning time, with everyone who voted for it saying Yes
+async fn close_poll(ctx: &Context, poll: &Poll) -> String {
+    let winner = match poll.winner(clock::now()) {
+        Some(winner) => winner,
+        None => {
+            return "Nobody voted for a time which is still to come, so no session was planned."
+                .to_string()
+        }
+    };
+    let time = poll.times[winner];
+
+    if ctx.is_session_present(poll.channel_id).await {
+        return format!(
+            "<t:{}:F> won, but there is already a session running in this channel, \
+             so it was not planned.",
+            time.timestamp()
+        );
+    }
+
+    let request = SessionRequest {
+        host: poll.host,
+        time,
+        description: poll.description.clone(),
+        max_players: poll.max_players,
+        temp_voice: false,
+        scheduled_event: false,
+    };
+    if let Err(why) = start_session(ctx, poll.channel_id, request).await {
+        warn!("Error starting session for poll: {}", why);
+        return format!("{} No session was planned.", why.user_message());
+    }
+
+    if let Some(session) = ctx.session(poll.channel_id).await {
+        for user_id in poll.voters(winner) {
+            let on_probation = is_on_probation(ctx, user_id).await;
+            session
+                .write()
+                .await
+                .respond(user_id, UserState::Will, on_probation);
+        }
+
+        ctx.save_sessions().await;
+        schedule_announcement_update(ctx, session).await;
+    }
+
+    format!(
+        "<t:{}:F> won with {} vote(s), the session has been planned!",
+        time.timestamp(),
+        poll.vote_count(winner)
+    )
+}
+
+/// Closes the polls whose deadline has passed, turning each into a session at its winning time
+pub async fn close_due_polls(ctx: &Context) {
+    let due = ctx
+        .data
+        .read()
+        .await
+        .get::<Polls>()
+        .expect("Error reading polls from TypeMap")
+        .write()
+        .await
+        .take_due(clock::now());
+
+    for poll in due {
+        let outcome = close_poll(ctx, &poll).await;
+
+        let mut content = format!("Voting has closed! {}\n", outcome);
+        for (idx, time) in poll.times.iter().enumerate() {
+            content += &format!(
+                "\n<t:{}:F>: {} vote(s)",
+                time.timestamp(),
+                poll.vote_count(idx)
+            );
+        }
+
+        if let Err(why) = ctx
+            .discord()
+            .await
+            .edit_message(poll.channel_id, poll.message_id, |message| {
+                message
+                    .content(content)
+                    .components(|components| components.set_action_rows(vec![]))
+            })
+            .await
+        {
+            warn!("Error closing poll message: {}", why);
+        }
+    }
+}
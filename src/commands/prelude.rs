@@ -1,14 +1,16 @@
-use chrono::Timelike;
 pub use serenity::{
     client::Context,
     model::interactions::{
-        Interaction, InteractionApplicationCommandCallbackDataFlags, InteractionResponseType,
+        message_component, Interaction, InteractionApplicationCommandCallbackDataFlags,
+        InteractionResponseType,
     },
     model::prelude::*,
 };
-pub use tracing::{error, info, warn};
+pub use tracing::warn;
 
-use crate::context_ext::ContextExt;
+pub use crate::error::{Error, Result};
+
+use crate::{clock, context_ext::ContextExt};
 
 pub async fn interaction_respond_with_private_message(
     ctx: &Context,
@@ -16,8 +18,10 @@ pub async fn interaction_respond_with_private_message(
     content: &str,
 ) {
     match interaction {
-        Interaction::ApplicationCommand(interaction) => interaction
-            .create_interaction_response(&ctx.http, |response| {
+        Interaction::ApplicationCommand(interaction) => ctx
+            .discord()
+            .await
+            .create_interaction_response(interaction.id, &interaction.token, |response| {
                 response
                     .kind(InteractionResponseType::ChannelMessageWithSource)
                     .interaction_response_data(|message| {
@@ -28,8 +32,10 @@ pub async fn interaction_respond_with_private_message(
             })
             .await
             .unwrap_or_else(|why| warn!("Error responding to interaction: {}", why)),
-        Interaction::MessageComponent(interaction) => interaction
-            .create_interaction_response(&ctx.http, |response| {
+        Interaction::MessageComponent(interaction) => ctx
+            .discord()
+            .await
+            .create_interaction_response(interaction.id, &interaction.token, |response| {
                 response
                     .kind(InteractionResponseType::ChannelMessageWithSource)
                     .interaction_response_data(|message| {
@@ -41,37 +47,100 @@ pub async fn interaction_respond_with_private_message(
             .await
             .unwrap_or_else(|why| warn!("Error responding to interaction: {}", why)),
         Interaction::Ping(_) => warn!("Cant respond to ping interaction!"),
+        Interaction::Autocomplete(_) => warn!("Cant respond to autocomplete interaction!"),
     }
 }
 
-pub async fn update_bot_status(ctx: &Context) {
-    if ctx.is_session_started().await {
-        let game = ctx.session().await.read().await.game.name.clone();
-        let content = format!("{} | Now!", game);
-        ctx.set_presence(Some(Activity::playing(content)), OnlineStatus::DoNotDisturb)
-            .await;
-    } else if ctx.is_session_present().await {
-        let game = ctx.session().await.read().await.game.name.clone();
-        let time = ctx.session().await.read().await.time.time();
-        let timezone = ctx.config().await.timezone_text;
+/// The option picked in a select menu whose values are indices into a list of `len` items.
+/// They can only be out of range if the interaction was forged, or the list changed since the menu was sent
+pub fn selected_index(
+    interaction: &message_component::MessageComponentInteraction,
+    len: usize,
+) -> Result<usize> {
+    interaction
+        .data
+        .values
+        .first()
+        .and_then(|value| value.parse::<usize>().ok())
+        .filter(|index| *index < len)
+        .ok_or(Error::InvalidInteraction("Select menu value out of range"))
+}
 
-        let hour = if time.hour() < 10 {
-            format!("0{}", time.hour())
-        } else {
-            time.hour().to_string()
-        };
-        let minute = if time.minute() < 10 {
-            format!("0{}", time.minute())
-        } else {
-            time.minute().to_string()
-        };
+/// Lets the user know their interaction failed. Handlers can fail after already responding,
+/// in which case the error is sent as a followup instead
+pub async fn interaction_respond_with_error(
+    ctx: &Context,
+    interaction_id: InteractionId,
+    token: &str,
+    content: &str,
+) {
+    let discord = ctx.discord().await;
+    let response = discord
+        .create_interaction_response(interaction_id, token, |response| {
+            response
+                .kind(InteractionResponseType::ChannelMessageWithSource)
+                .interaction_response_data(|message| {
+                    message
+                        .content(content)
+                        .flags(InteractionApplicationCommandCallbackDataFlags::EPHEMERAL)
+                })
+        })
+        .await;
 
-        let content = format!("{} | {}:{} {}", game, hour, minute, timezone);
-        ctx.set_presence(Some(Activity::playing(content)), OnlineStatus::Idle)
-            .await;
-    } else {
+    if response.is_err() {
+        discord
+            .create_followup_message(token, |message| {
+                message
+                    .content(content)
+                    .flags(InteractionApplicationCommandCallbackDataFlags::EPHEMERAL)
+            })
+            .await
+            .unwrap_or_else(|why| warn!("Error sending error message: {}", why));
+    }
+}
+
+pub async fn update_bot_status(ctx: &Context) {
+    let mut sessions = vec![];
+    for session in ctx.sessions().await {
+        let session = session.read().await;
+        sessions.push((session.game.name.clone(), session.time));
+    }
+    sessions.sort_by_key(|(_, time)| *time);
+
+    if sessions.is_empty() {
         let content = ctx.config().await.idle_text;
         ctx.set_presence(Some(Activity::playing(content)), OnlineStatus::Online)
             .await;
+        return;
     }
+
+    let now = clock::now();
+    let config = ctx.config().await;
+    let content = sessions
+        .iter()
+        .map(|(game, time)| {
+            let time = time.with_timezone(&config.timezone);
+            let timezone = match &config.timezone_text {
+                Some(text) => text.clone(),
+                None => time.format("%Z").to_string(),
+            };
+
+            if time <= now {
+                format!("{} | Now!", game)
+            } else {
+                format!("{} | {} {}", game, time.format("%H:%M"), timezone)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    // DoNotDisturb while any of the sessions is being played, Idle while they are all still planned
+    let status = if sessions.iter().any(|(_, time)| *time <= now) {
+        OnlineStatus::DoNotDisturb
+    } else {
+        OnlineStatus::Idle
+    };
+
+    ctx.set_presence(Some(Activity::playing(content)), status)
+        .await;
 }
@@ -0,0 +1,176 @@
+use crate::{
+    announcement::update_announcement,
+    commands::{
+        endhost::can_cancel_session, hostgame::resolve_session_time, prelude::*,
+        status::no_session_message,
+    },
+    context_ext::ContextExt,
+    guild_event::move_event,
+    interaction_handler::{CommandHandler, InteractionHandler},
+    session::UserState,
+    timer::spawn_session_task,
+};
+
+use serenity::{
+    async_trait,
+    client::Context,
+    model::interactions::application_command::{
+        ApplicationCommandInteraction, ApplicationCommandInteractionDataOptionValue,
+        ApplicationCommandOptionType,
+    },
+};
+
+#[derive(Clone, Copy)]
+pub struct Reschedule;
+
+impl InteractionHandler for Reschedule {
+    fn name(&self) -> &'static str {
+        "reschedule"
+    }
+}
+
+#[async_trait]
+impl CommandHandler for Reschedule {
+    async fn invoke(&self, ctx: Context, interaction: ApplicationCommandInteraction) -> Result<()> {
+        let session = match ctx.session(interaction.channel_id).await {
+            Some(session) => session,
+            None => {
+                let content = no_session_message(&ctx).await;
+                interaction_respond_with_private_message(
+                    &ctx,
+                    &Interaction::ApplicationCommand(interaction),
+                    &content,
+                )
+                .await;
+                return Ok(());
+            }
+        };
+
+        let user_id = interaction.user.id;
+        let member = interaction.member.as_ref();
+        if !can_cancel_session(&ctx, &*session.read().await, user_id, member).await {
+            interaction_respond_with_private_message(
+                &ctx,
+                &Interaction::ApplicationCommand(interaction),
+                "You don't have permissions to reschedule this session!",
+            )
+            .await;
+            return Ok(());
+        }
+
+        if session.read().await.is_started() {
+            interaction_respond_with_private_message(
+                &ctx,
+                &Interaction::ApplicationCommand(interaction),
+                "This session has already started!",
+            )
+            .await;
+            return Ok(());
+        }
+
+        let time = interaction
+            .data
+            .options
+            .iter()
+            .find(|option| option.name == "time")
+            .and_then(|option| match option.resolved.as_ref() {
+                Some(ApplicationCommandInteractionDataOptionValue::String(s)) => Some(s.clone()),
+                _ => None,
+            });
+
+        let (new_time, timezone) = match resolve_session_time(&ctx, user_id, time).await {
+            Ok(resolved) => resolved,
+            Err(why) => {
+                interaction_respond_with_private_message(
+                    &ctx,
+                    &Interaction::ApplicationCommand(interaction),
+                    &why.to_string(),
+                )
+                .await;
+                return Ok(());
+            }
+        };
+
+        let config = ctx.config().await;
+        let (channel_id, game, event_id) = {
+            let mut session = session.write().await;
+            let channel_id = session.channel_id();
+
+            // The old task would still send its reminders for the old time
+            session.handle.abort();
+            session.handle = spawn_session_task(
+                &ctx,
+                channel_id,
+                config.guild_id,
+                new_time,
+                config.reminders_for(&session.game),
+            );
+            session.time = new_time;
+
+            (channel_id, session.game.clone(), session.event_id)
+        };
+
+        ctx.save_sessions().await;
+        if let Some(event_id) = event_id {
+            move_event(&ctx, event_id, new_time).await;
+        }
+        update_bot_status(&ctx).await;
+        update_announcement(&ctx, &session).await;
+
+        interaction_respond_with_private_message(
+            &ctx,
+            &Interaction::ApplicationCommand(interaction),
+            &format!(
+                "The session has been moved to <t:{0}:F> (<t:{0}:R>), read as {1} in {2}",
+                new_time.timestamp(),
+                new_time.with_timezone(&timezone).format("%A %H:%M"),
+                timezone.name()
+            ),
+        )
+        .await;
+
+        let pings = session
+            .read()
+            .await
+            .users
+            .iter()
+            .filter(|(_, state)| **state != UserState::Wont)
+            .fold(String::new(), |lhs, (rhs, _)| {
+                lhs + format!("<@{}> ", rhs).as_str()
+            });
+
+        if let Err(why) = ctx
+            .discord()
+            .await
+            .send_message(channel_id, |message| {
+                message.content(format!(
+                    "{}The {} session has been moved to <t:{}:F>!",
+                    pings,
+                    game.name,
+                    new_time.timestamp()
+                ))
+            })
+            .await
+        {
+            warn!("Error sending message to channel: {}", why);
+        }
+
+        Ok(())
+    }
+
+    fn create_command(
+        self,
+        command: &mut serenity::builder::CreateApplicationCommand,
+    ) -> &mut serenity::builder::CreateApplicationCommand {
+        command
+            .name(self.name())
+            .description("Moves this channel's session to another time, keeping everyone's answers")
+            .create_option(|option| {
+                option
+                    .kind(ApplicationCommandOptionType::String)
+                    .name("time")
+                    .description("The new time, e.g. 21:00, friday 20:30, in 45m")
+                    .required(true)
+            })
+    }
+}
@@ -15,38 +15,29 @@ use serenity::{
     },
 };
 
-async fn get_select_menu_option(
-    ctx: &Context,
-    game: &Game,
-    member: &Member,
-    idx: usize,
-) -> Option<CreateSelectMenuOption> {
-    let role_id = game.role_id;
-
-    if let Some(roles) = member.roles(&ctx.cache).await {
-        let is_set = roles.iter().any(|role| role.id == role_id);
-        let is_set = if is_set { "" } else { "not " };
-
-        Some(
-            CreateSelectMenuOption::default()
-                .label(&game.name)
-                .description(format!("This role is {}set", is_set))
-                .value(idx)
-                .to_owned(),
-        )
+fn get_select_menu_option(game: &Game, roles: &[RoleId], idx: usize) -> CreateSelectMenuOption {
+    let is_set = if roles.contains(&game.role_id) {
+        ""
     } else {
-        None
-    }
+        "not "
+    };
+
+    CreateSelectMenuOption::default()
+        .label(&game.name)
+        .description(format!("This role is {}set", is_set))
+        .value(idx)
+        .to_owned()
 }
 
-async fn get_action_row(ctx: &Context, member: &Member) -> CreateActionRow {
-    let mut options_vec = vec![];
-
-    for (idx, game) in ctx.config().await.games.iter().enumerate() {
-        if let Some(option) = get_select_menu_option(&ctx, &game, &member, idx).await {
-            options_vec.push(option);
-        }
-    }
+async fn get_action_row(ctx: &Context, roles: &[RoleId]) -> CreateActionRow {
+    let options_vec = ctx
+        .config()
+        .await
+        .games
+        .iter()
+        .enumerate()
+        .map(|(idx, game)| get_select_menu_option(game, roles, idx))
+        .collect();
 
     CreateActionRow::default()
         .create_select_menu(|menu| {
@@ -67,11 +58,12 @@ impl InteractionHandler for RolesCommand {
 
 #[async_trait]
 impl CommandHandler for RolesCommand {
-    async fn invoke(&self, ctx: Context, interaction: ApplicationCommandInteraction) {
+    async fn invoke(&self, ctx: Context, interaction: ApplicationCommandInteraction) -> Result<()> {
         if let Some(member) = &interaction.member {
-            let action_row = get_action_row(&ctx, &member).await;
-            interaction
-                .create_interaction_response(&ctx.http, |response| {
+            let action_row = get_action_row(&ctx, &member.roles).await;
+            ctx.discord()
+                .await
+                .create_interaction_response(interaction.id, &interaction.token, |response| {
                     response
                         .kind(InteractionResponseType::ChannelMessageWithSource)
                         .interaction_response_data(|message| {
@@ -81,9 +73,10 @@ impl CommandHandler for RolesCommand {
                                 .flags(InteractionApplicationCommandCallbackDataFlags::EPHEMERAL)
                         })
                 })
-                .await
-                .unwrap_or_else(|why| warn!("Error responding to interaction: {}", why));
+                .await?;
         }
+
+        Ok(())
     }
 
     fn create_command(
@@ -105,37 +98,41 @@ impl InteractionHandler for MenuHandler {
 
 #[async_trait]
 impl MessageHandler for MenuHandler {
-    async fn invoke(&self, ctx: Context, interaction: MessageComponentInteraction) {
-        // The conversion should always be valid unless a request is forged via modifications
-        // This is due to the fact .values[0] will always be a value set via get_action_row()
-        let index = interaction.clone().data.values[0]
-            .parse::<usize>()
-            .expect("Error parsing role data to usize");
-
-        let role_id = ctx.config().await.games[index].role_id;
-        let mut member = interaction.member.clone().expect("Error retrieving member");
-        let roles = member
-            .roles(&ctx.cache)
-            .await
-            .expect("Error retrieving roles");
-
-        let action = if roles.iter().any(|role| role.id == role_id) {
-            member
-                .remove_role(&ctx.http, role_id)
+    async fn invoke(&self, ctx: Context, interaction: MessageComponentInteraction) -> Result<()> {
+        // The values are the ones set via get_action_row()
+        let games = ctx.config().await.games;
+        let role_id = games[selected_index(&interaction, games.len())?].role_id;
+        let (guild_id, member) = match (interaction.guild_id, interaction.member.as_ref()) {
+            (Some(guild_id), Some(member)) => (guild_id, member),
+            _ => {
+                return Err(Error::InvalidInteraction(
+                    "Roles menu used outside of a guild",
+                ))
+            }
+        };
+        let user_id = interaction.user.id;
+        let mut roles = member.roles.clone();
+
+        let discord = ctx.discord().await;
+        let action = if roles.contains(&role_id) {
+            discord
+                .remove_role(guild_id, user_id, role_id)
                 .await
                 .unwrap_or_else(|why| warn!("Error removing role: {}", why));
+            roles.retain(|role| *role != role_id);
             "un"
         } else {
-            member
-                .add_role(&ctx.http, role_id)
+            discord
+                .add_role(guild_id, user_id, role_id)
                 .await
                 .unwrap_or_else(|why| warn!("Error adding role: {}", why));
+            roles.push(role_id);
             ""
         };
 
-        let action_row = get_action_row(&ctx, &member).await;
-        interaction
-            .create_interaction_response(&ctx.http, |response| {
+        let action_row = get_action_row(&ctx, &roles).await;
+        discord
+            .create_interaction_response(interaction.id, &interaction.token, |response| {
                 response
                     .kind(InteractionResponseType::UpdateMessage)
                     .interaction_response_data(|message| {
@@ -144,7 +141,8 @@ impl MessageHandler for MenuHandler {
                             .components(|components| components.add_action_row(action_row))
                     })
             })
-            .await
-            .unwrap_or_else(|why| warn!("Error responding to interaction: {}", why));
+            .await?;
+
+        Ok(())
     }
 }
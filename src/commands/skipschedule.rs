@@ -0,0 +1,120 @@
+use crate::{
+    clock,
+    commands::prelude::*,
+    config::Capability,
+    context_ext::ContextExt,
+    interaction_handler::{CommandHandler, InteractionHandler},
+    scheduler::{next_occurrence, Occurrence, ScheduleState},
+};
+
+use chrono::Duration;
+use serenity::{
+    async_trait,
+    client::Context,
+    model::interactions::application_command::{
+        ApplicationCommandInteraction, ApplicationCommandInteractionDataOptionValue,
+        ApplicationCommandOptionType,
+    },
+};
+
+#[derive(Clone, Copy)]
+pub struct SkipSchedule;
+
+impl InteractionHandler for SkipSchedule {
+    fn name(&self) -> &'static str {
+        "skipschedule"
+    }
+
+    fn capability(&self) -> Option<Capability> {
+        Some(Capability::ManageSchedules)
+    }
+}
+
+#[async_trait]
+impl CommandHandler for SkipSchedule {
+    async fn invoke(&self, ctx: Context, interaction: ApplicationCommandInteraction) -> Result<()> {
+        let config = ctx.config().await;
+
+        let game = interaction
+            .data
+            .options
+            .iter()
+            .find(|option| option.name == "game")
+            .and_then(|option| match option.resolved.as_ref() {
+                Some(ApplicationCommandInteractionDataOptionValue::String(s)) => Some(s.clone()),
+                _ => None,
+            })
+            .unwrap_or_default();
+
+        let state = ctx
+            .data
+            .read()
+            .await
+            .get::<ScheduleState>()
+            .expect("Error reading schedule state from TypeMap")
+            .clone();
+
+        // The earliest occurrence of any of the game's schedules which wasn't posted or skipped yet
+        let mut next: Option<Occurrence> = None;
+        for schedule in config
+            .schedules
+            .iter()
+            .filter(|schedule| schedule.game.eq_ignore_ascii_case(&game))
+        {
+            let mut from = clock::now();
+            while let Some(time) = next_occurrence(schedule, from.with_timezone(&config.timezone)) {
+                let occurrence = Occurrence {
+                    game: schedule.game.clone(),
+                    time,
+                };
+
+                if !state.read().await.is_handled(&occurrence) {
+                    if next.as_ref().is_none_or(|next| occurrence.time < next.time) {
+                        next = Some(occurrence);
+                    }
+                    break;
+                }
+
+                from = time + Duration::minutes(1);
+            }
+        }
+
+        let content = match next {
+            Some(occurrence) => {
+                let content = format!(
+                    "The {} session on <t:{}:F> has been skipped!",
+                    occurrence.game,
+                    occurrence.time.timestamp()
+                );
+                state.write().await.mark_handled(occurrence);
+                content
+            }
+            None => format!("There is no upcoming scheduled session for \"{}\"!", game),
+        };
+
+        interaction_respond_with_private_message(
+            &ctx,
+            &Interaction::ApplicationCommand(interaction),
+            &content,
+        )
+        .await;
+
+        Ok(())
+    }
+
+    fn create_command(
+        self,
+        command: &mut serenity::builder::CreateApplicationCommand,
+    ) -> &mut serenity::builder::CreateApplicationCommand {
+        command
+            .name(self.name())
+            .description("Skips the next scheduled session of a game")
+            .create_option(|option| {
+                option
+                    .kind(ApplicationCommandOptionType::String)
+                    .name("game")
+                    .description("Name of the game whose next session to skip")
+                    .required(true)
+            })
+    }
+}
@@ -0,0 +1,278 @@
+use std::{collections::HashMap, fmt::Display, hash::Hash};
+
+use crate::{
+    attendance::format_duration,
+    clock,
+    commands::prelude::*,
+    context_ext::ContextExt,
+    history::{ArchivedSession, History},
+    interaction_handler::{CommandHandler, InteractionHandler},
+    time_parse::parse_duration,
+};
+
+use chrono::Duration;
+use serenity::{
+    async_trait,
+    builder::CreateEmbed,
+    client::Context,
+    model::{
+        id::UserId,
+        interactions::application_command::{
+            ApplicationCommandInteraction, ApplicationCommandInteractionDataOptionValue,
+            ApplicationCommandOptionType,
+        },
+    },
+    utils::Colour,
+};
+
+#[derive(Clone, Copy)]
+pub struct Stats;
+
+impl InteractionHandler for Stats {
+    fn name(&self) -> &'static str {
+        "stats"
+    }
+}
+
+// "1. first (3)\n2. second (2)", for the `amount` entries with the highest counts
+fn top<K: Display + Eq + Hash>(counts: HashMap<K, usize>, amount: usize) -> String {
+    let mut counts = counts.into_iter().collect::<Vec<_>>();
+    counts.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+
+    if counts.is_empty() {
+        return "Nobody".to_string();
+    }
+
+    counts
+        .iter()
+        .take(amount)
+        .enumerate()
+        .map(|(idx, (key, count))| format!("{}. {} ({})", idx + 1, key, count))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn percentage(part: usize, total: usize) -> String {
+    match (part * 100).checked_div(total) {
+        Some(percentage) => format!("{}%", percentage),
+        None => "-".to_string(),
+    }
+}
+
+fn user_stats(embed: &mut CreateEmbed, sessions: &[&ArchivedSession], user_id: UserId) {
+    let played = sessions
+        .iter()
+        .filter(|session| session.started)
+        .collect::<Vec<_>>();
+    let attended = played
+        .iter()
+        .filter(|session| session.attended(user_id))
+        .count();
+    let said_yes = played
+        .iter()
+        .filter(|session| session.said_yes(user_id))
+        .count();
+    let showed_up = played
+        .iter()
+        .filter(|session| session.said_yes(user_id) && session.attended(user_id))
+        .count();
+    let hosted = sessions
+        .iter()
+        .filter(|session| session.host == user_id)
+        .count();
+    let time_in_voice = played.iter().fold(Duration::zero(), |total, session| {
+        total + session.time_in_voice(user_id)
+    });
+
+    embed
+        .description(format!("<@{}>", user_id))
+        .field("Sessions attended", attended, true)
+        .field("Time in voice", format_duration(time_in_voice), true)
+        .field("Sessions hosted", hosted, true)
+        .field(
+            "Reliability",
+            format!(
+                "Showed up to {}/{} sessions after saying Yes ({})",
+                showed_up,
+                said_yes,
+                percentage(showed_up, said_yes)
+            ),
+            false,
+        )
+        .field("No-shows", said_yes - showed_up, true);
+}
+
+fn game_stats(embed: &mut CreateEmbed, sessions: &[&ArchivedSession]) {
+    let played = sessions
+        .iter()
+        .filter(|session| session.started)
+        .collect::<Vec<_>>();
+    let cancelled = sessions.len() - played.len();
+    let play_time = played.iter().fold(Duration::zero(), |total, session| {
+        total + session.duration()
+    });
+    let attendees = played
+        .iter()
+        .map(|session| session.attendance.len())
+        .sum::<usize>();
+
+    let mut players = HashMap::new();
+    for session in &played {
+        for user_id in session.attendance.keys() {
+            *players.entry(format!("<@{}>", user_id)).or_insert(0) += 1;
+        }
+    }
+
+    embed
+        .field("Sessions played", played.len(), true)
+        .field("Sessions cancelled", cancelled, true)
+        .field("Time played", format_duration(play_time), true)
+        .field(
+            "Average attendance",
+            if played.is_empty() {
+                "-".to_string()
+            } else {
+                format!("{:.1}", attendees as f64 / played.len() as f64)
+            },
+            true,
+        )
+        .field("Most active players", top(players, 5), false);
+}
+
+fn overall_stats(embed: &mut CreateEmbed, sessions: &[&ArchivedSession]) {
+    let mut games = HashMap::new();
+    let mut hosts = HashMap::new();
+    let mut players = HashMap::new();
+
+    for session in sessions.iter().filter(|session| session.started) {
+        *games.entry(session.game.clone()).or_insert(0) += 1;
+        *hosts.entry(format!("<@{}>", session.host)).or_insert(0) += 1;
+        for user_id in session.attendance.keys() {
+            *players.entry(format!("<@{}>", user_id)).or_insert(0) += 1;
+        }
+    }
+
+    embed
+        .field(
+            "Sessions played",
+            sessions.iter().filter(|session| session.started).count(),
+            false,
+        )
+        .field("Most played games", top(games, 5), true)
+        .field("Most active hosts", top(hosts, 5), true)
+        .field("Most active players", top(players, 5), true);
+}
+
+#[async_trait]
+impl CommandHandler for Stats {
+    async fn invoke(&self, ctx: Context, interaction: ApplicationCommandInteraction) -> Result<()> {
+        let mut period = None;
+        let mut user = None;
+        let mut game = None;
+
+        for option in &interaction.data.options {
+            match (option.name.as_str(), option.resolved.as_ref()) {
+                ("period", Some(ApplicationCommandInteractionDataOptionValue::String(s))) => {
+                    period = Some(s.clone())
+                }
+                ("user", Some(ApplicationCommandInteractionDataOptionValue::User(u, _))) => {
+                    user = Some(u.id)
+                }
+                ("game", Some(ApplicationCommandInteractionDataOptionValue::String(s))) => {
+                    game = Some(s.clone())
+                }
+                _ => {}
+            }
+        }
+
+        let since = match period.as_deref().map(|period| {
+            parse_duration(period).and_then(|period| clock::now().checked_sub_signed(period))
+        }) {
+            None => None,
+            Some(Some(since)) => Some(since),
+            Some(None) => {
+                interaction_respond_with_private_message(
+                    &ctx,
+                    &Interaction::ApplicationCommand(interaction),
+                    "I couldn't understand that period! Try something like \"7d\" or \"30d\"",
+                )
+                .await;
+                return Ok(());
+            }
+        };
+
+        let history = ctx
+            .data
+            .read()
+            .await
+            .get::<History>()
+            .expect("Error reading history from TypeMap")
+            .clone();
+        let history = history.read().await;
+        let sessions = history
+            .since(since)
+            .into_iter()
+            .filter(|session| {
+                game.as_ref()
+                    .is_none_or(|game| session.game.eq_ignore_ascii_case(game))
+            })
+            .collect::<Vec<_>>();
+
+        let title = match (&game, &period) {
+            (Some(game), Some(period)) => format!("{} stats for the last {}", game, period),
+            (Some(game), None) => format!("{} stats", game),
+            (None, Some(period)) => format!("Stats for the last {}", period),
+            (None, None) => "Stats".to_string(),
+        };
+
+        let mut embed = CreateEmbed::default();
+        embed.title(title).colour(Colour::from_rgb(244, 173, 249));
+        match (user, &game) {
+            (Some(user_id), _) => user_stats(&mut embed, &sessions, user_id),
+            (None, Some(_)) => game_stats(&mut embed, &sessions),
+            (None, None) => overall_stats(&mut embed, &sessions),
+        }
+
+        ctx.discord()
+            .await
+            .create_interaction_response(interaction.id, &interaction.token, |response| {
+                response
+                    .kind(InteractionResponseType::ChannelMessageWithSource)
+                    .interaction_response_data(|message| {
+                        message
+                            .add_embed(embed)
+                            .flags(InteractionApplicationCommandCallbackDataFlags::EPHEMERAL)
+                    })
+            })
+            .await?;
+
+        Ok(())
+    }
+
+    fn create_command(
+        self,
+        command: &mut serenity::builder::CreateApplicationCommand,
+    ) -> &mut serenity::builder::CreateApplicationCommand {
+        command
+            .name(self.name())
+            .description("Shows statistics about past sessions")
+            .create_option(|option| {
+                option
+                    .kind(ApplicationCommandOptionType::String)
+                    .name("period")
+                    .description("How far back to look, e.g. 7d or 30d. All time when left out")
+            })
+            .create_option(|option| {
+                option
+                    .kind(ApplicationCommandOptionType::User)
+                    .name("user")
+                    .description("Shows the stats of this user")
+            })
+            .create_option(|option| {
+                option
+                    .kind(ApplicationCommandOptionType::String)
+                    .name("game")
+                    .description("Only counts sessions of this game")
+            })
+    }
+}
@@ -1,27 +1,34 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, sync::Arc};
 
 use crate::{
+    clock,
     commands::prelude::interaction_respond_with_private_message,
     context_ext::ContextExt,
+    error::Result,
+    history::History,
     interaction_handler::{CommandHandler, InteractionHandler},
-    session::UserState,
+    session::{Session, UserState},
+    time_parse::parse_duration,
 };
 
-use chrono::{Duration, Local};
+use chrono::Duration;
 use serenity::{
     async_trait,
     builder::CreateEmbed,
     client::Context,
     model::{
-        id::{GuildId, UserId},
+        id::{ChannelId, GuildId, UserId},
         interactions::{
             application_command::ApplicationCommandInteraction, Interaction,
             InteractionApplicationCommandCallbackDataFlags, InteractionResponseType,
         },
     },
+    prelude::RwLock,
     utils::Colour,
 };
-use tracing::log::warn;
+
+// Discord refuses embeds with a field longer than this
+const MAX_FIELD_LENGTH: usize = 1024;
 
 #[derive(Clone, Copy)]
 pub struct Status;
@@ -52,35 +59,147 @@ pub fn users_with_state(user_map: &HashMap<UserId, UserState>, state: UserState)
     }
 }
 
-pub async fn get_status_embed(ctx: &Context, guild_id: GuildId) -> CreateEmbed {
-    let session = ctx.session().await;
+// Joins the entries of an embed field, replacing the ones which don't fit with how many were left out
+fn field_value(entries: &[String], separator: &str) -> String {
+    let mut value = String::new();
+    for (shown, entry) in entries.iter().enumerate() {
+        let joined = if shown == 0 {
+            entry.clone()
+        } else {
+            format!("{}{}{}", value, separator, entry)
+        };
+
+        // There has to be room left to say how many more there are
+        let rest = entries.len() - shown - 1;
+        let more = if rest == 0 {
+            String::new()
+        } else {
+            format!("{}…and {} more", separator, rest)
+        };
+        if joined.chars().count() + more.chars().count() > MAX_FIELD_LENGTH {
+            let separator = if shown == 0 { "" } else { separator };
+            return format!("{}{}…and {} more", value, separator, entries.len() - shown);
+        }
+        value = joined;
+    }
+    value
+}
+
+// Mentions everyone with the given state for an embed field, along with how many there are
+fn field_with_state(user_map: &HashMap<UserId, UserState>, state: UserState) -> (String, usize) {
+    let mentions = user_map
+        .iter()
+        .filter(|(_, s)| **s == state)
+        .map(|(user_id, _)| format!("<@{}>", user_id))
+        .collect::<Vec<_>>();
+
+    if mentions.is_empty() {
+        ("Nobody".to_string(), 0)
+    } else {
+        (field_value(&mentions, ", "), mentions.len())
+    }
+}
+
+// The embed every session embed is built on: the host and everyone's responses
+async fn get_session_embed(
+    ctx: &Context,
+    session: &Arc<RwLock<Session>>,
+    guild_id: GuildId,
+    title: &str,
+) -> CreateEmbed {
     let user_map = session.read().await.users.clone();
-    let host = session
-        .read()
-        .await
-        .host
-        .to_user(&ctx.http)
-        .await
-        .unwrap_or_default();
+    let waitlist = session.read().await.waitlist.clone();
+    let max_players = session.read().await.max_players;
+    let hosts = session.read().await.hosts();
 
-    let host_nick = host
-        .nick_in(&ctx.http, guild_id)
-        .await
-        .unwrap_or_else(|| host.name.clone());
+    let discord = ctx.discord().await;
+    let mut host_members = vec![];
+    for user_id in &hosts {
+        let member = discord.member(guild_id, *user_id).await.unwrap_or_default();
+        host_members.push(member);
+    }
+    let host_nicks = host_members
+        .iter()
+        .map(|member| member.name.as_str())
+        .collect::<Vec<_>>();
+
+    let (will_join, will_join_amount) = field_with_state(&user_map, UserState::Will);
+    let (may_join, may_join_amount) = field_with_state(&user_map, UserState::May);
+    let (wont_join, wont_join_amount) = field_with_state(&user_map, UserState::Wont);
+
+    let will_join_amount = match max_players {
+        Some(max_players) => format!("{}/{}", will_join_amount, max_players),
+        None => will_join_amount.to_string(),
+    };
 
-    let (will_join, will_join_amount) = users_with_state(&user_map, UserState::Will);
-    let (may_join, may_join_amount) = users_with_state(&user_map, UserState::May);
-    let (wont_join, wont_join_amount) = users_with_state(&user_map, UserState::Wont);
+    let mut embed = CreateEmbed::default();
+    embed
+        .title(title)
+        .colour(Colour::from_rgb(244, 173, 249))
+        .author(|author| {
+            author.name(host_nicks.join(", "));
+            // The icon can only show one of them, so it shows the main host, if they could be fetched
+            if !host_members[0].avatar_url.is_empty() {
+                author.icon_url(&host_members[0].avatar_url);
+            }
+            author
+        })
+        .field(
+            format!("People who are sure: {}", will_join_amount),
+            will_join,
+            false,
+        )
+        .field(
+            format!("People who are unsure: {}", may_join_amount),
+            may_join,
+            false,
+        )
+        .field(
+            format!("People who dont want to: {}", wont_join_amount),
+            wont_join,
+            false,
+        );
+
+    if !waitlist.is_empty() {
+        let waiting = waitlist
+            .iter()
+            .enumerate()
+            .map(|(idx, user_id)| format!("{}. <@{}>", idx + 1, user_id))
+            .collect::<Vec<_>>();
+        embed.field(
+            format!("Waitlist: {}", waitlist.len()),
+            field_value(&waiting, ", "),
+            false,
+        );
+    }
 
+    embed
+}
+
+pub async fn get_status_embed(
+    ctx: &Context,
+    session: &Arc<RwLock<Session>>,
+    guild_id: GuildId,
+) -> CreateEmbed {
     let time = session.read().await.time;
-    let time_left = time - Local::now();
+    let time_left = time - clock::now();
     let time_str = if time_left < Duration::zero() {
         "Already started!".to_string()
     } else {
+        let days_left = time_left.num_days();
         let hours_left = time_left.num_hours() % 24;
         let minutes_left = time_left.num_minutes() % 60;
         format!(
-            "{}{} minute{} | <t:{}>",
+            "{}{}{} minute{} | <t:{}>",
+            if days_left > 0 {
+                format!(
+                    "{} day{}, ",
+                    days_left,
+                    if days_left == 1 { "" } else { "s" }
+                )
+            } else {
+                "".to_string()
+            },
             if hours_left > 0 {
                 format!(
                     "{} hour{} and ",
@@ -96,46 +215,150 @@ pub async fn get_status_embed(ctx: &Context, guild_id: GuildId) -> CreateEmbed {
         )
     };
 
-    CreateEmbed::default()
-        .title("Session Status")
-        .colour(Colour::from_rgb(244, 173, 249))
-        .author(|author| author.name(host_nick).icon_url(host.face()))
-        .field(
-            format!("People who are sure: {}", will_join_amount),
-            will_join,
-            false,
-        )
-        .field(
-            format!("People who are unsure: {}", may_join_amount),
-            may_join,
-            false,
-        )
-        .field(
-            format!("People who dont want to: {}", wont_join_amount),
-            wont_join,
+    let mut embed = get_session_embed(ctx, session, guild_id, "Session Status").await;
+    embed.field("Time left until start", time_str, false);
+    embed
+}
+
+/// The status shown on the session's announcement. The message is only edited when the session
+/// changes, so the start is a Discord timestamp, which counts down on its own
+pub async fn get_announcement_embed(
+    ctx: &Context,
+    session: &Arc<RwLock<Session>>,
+    guild_id: GuildId,
+) -> CreateEmbed {
+    let timestamp = session.read().await.time.timestamp();
+
+    let mut embed = get_session_embed(ctx, session, guild_id, "Session Status").await;
+    embed.field(
+        "Starts",
+        format!("<t:{}:R> | <t:{}:F>", timestamp, timestamp),
+        false,
+    );
+    embed
+}
+
+/// The final state of a session which has been ended or cancelled, `action` says which
+pub async fn get_summary_embed(
+    ctx: &Context,
+    session: &Arc<RwLock<Session>>,
+    guild_id: GuildId,
+    action: &str,
+) -> CreateEmbed {
+    let time = session.read().await.time;
+    let title = format!("Session {}", action);
+
+    let mut embed = get_session_embed(ctx, session, guild_id, &title).await;
+    embed.field("Planned for", format!("<t:{}:F>", time.timestamp()), false);
+
+    let played = clock::now() - time;
+    if played > Duration::zero() {
+        let hours = played.num_hours();
+        let minutes = played.num_minutes() % 60;
+        embed.field(
+            "Played for",
+            format!(
+                "{} hour{} and {} minute{}",
+                hours,
+                if hours == 1 { "" } else { "s" },
+                minutes,
+                if minutes == 1 { "" } else { "s" }
+            ),
             false,
+        );
+    }
+
+    embed
+}
+
+// Tells the user there is no session in this channel, pointing them at the channels which do have one
+pub async fn no_session_message(ctx: &Context) -> String {
+    let mut channels: Vec<ChannelId> = vec![];
+    for session in ctx.sessions().await {
+        channels.push(session.read().await.channel_id());
+    }
+
+    if channels.is_empty() {
+        "No session currently running!".to_string()
+    } else {
+        let channels = channels
+            .iter()
+            .map(|channel_id| format!("<#{}>", channel_id))
+            .collect::<Vec<_>>()
+            .join(", ");
+        format!(
+            "No session currently running in this channel! Check out {}",
+            channels
         )
-        .field("Time left until start", time_str, false)
-        .clone()
+    }
+}
+
+// The reliability of the people who are sure, over the no-show policy's period or all time without one
+async fn reliability_of_sure(ctx: &Context, session: &Arc<RwLock<Session>>) -> String {
+    let since = ctx
+        .config()
+        .await
+        .no_show_policy
+        .and_then(|policy| parse_duration(&policy.period))
+        .map(|period| clock::now() - period);
+    let users = session.read().await.users.clone();
+    let history = ctx
+        .data
+        .read()
+        .await
+        .get::<History>()
+        .expect("Error reading history from TypeMap")
+        .clone();
+    let history = history.read().await;
+
+    let lines = users
+        .iter()
+        .filter(|(_, state)| **state == UserState::Will)
+        .filter_map(|(user_id, _)| match history.reliability(*user_id, since) {
+            (_, 0) => None,
+            (showed_up, said_yes) => Some(format!(
+                "<@{}>: {}/{} ({}%)",
+                user_id,
+                showed_up,
+                said_yes,
+                showed_up * 100 / said_yes
+            )),
+        })
+        .collect::<Vec<_>>();
+    field_value(&lines, "\n")
 }
 
 #[async_trait]
 impl CommandHandler for Status {
-    async fn invoke(&self, ctx: Context, interaction: ApplicationCommandInteraction) {
-        if !ctx.is_session_present().await {
-            interaction_respond_with_private_message(
-                &ctx,
-                &Interaction::ApplicationCommand(interaction),
-                "No session currently running!",
-            )
-            .await;
-            return;
-        }
+    async fn invoke(&self, ctx: Context, interaction: ApplicationCommandInteraction) -> Result<()> {
+        let session = match ctx.session(interaction.channel_id).await {
+            Some(session) => session,
+            None => {
+                let content = no_session_message(&ctx).await;
+                interaction_respond_with_private_message(
+                    &ctx,
+                    &Interaction::ApplicationCommand(interaction),
+                    &content,
+                )
+                .await;
+                return Ok(());
+            }
+        };
 
-        let embed = get_status_embed(&ctx, interaction.guild_id.unwrap_or_default()).await;
+        let mut embed =
+            get_status_embed(&ctx, &session, interaction.guild_id.unwrap_or_default()).await;
+        let reliability = reliability_of_sure(&ctx, &session).await;
+        if !reliability.is_empty() {
+            embed.field(
+                "How often they showed up after saying Yes",
+                reliability,
+                false,
+            );
+        }
 
-        interaction
-            .create_interaction_response(&ctx.http, |response| {
+        ctx.discord()
+            .await
+            .create_interaction_response(interaction.id, &interaction.token, |response| {
                 response
                     .kind(InteractionResponseType::ChannelMessageWithSource)
                     .interaction_response_data(|message| {
@@ -144,8 +367,9 @@ impl CommandHandler for Status {
                             .flags(InteractionApplicationCommandCallbackDataFlags::EPHEMERAL)
                     })
             })
-            .await
-            .unwrap_or_else(|why| warn!("Error handling invocation: {}", why));
+            .await?;
+
+        Ok(())
     }
 
     fn create_command(
@@ -154,6 +378,6 @@ impl CommandHandler for Status {
     ) -> &mut serenity::builder::CreateApplicationCommand {
         command
             .name(self.name())
-            .description("Status of the current game session")
+            .description("Status of this channel's game session")
     }
 }
@@ -0,0 +1,126 @@
+use std::{collections::HashMap, path::Path, sync::Arc};
+
+use crate::{
+    commands::prelude::*,
+    context_ext::ContextExt,
+    interaction_handler::{CommandHandler, InteractionHandler},
+    store,
+};
+
+use chrono_tz::Tz;
+use serenity::{
+    async_trait,
+    client::Context,
+    model::interactions::application_command::{
+        ApplicationCommandInteraction, ApplicationCommandInteractionDataOptionValue,
+        ApplicationCommandOptionType,
+    },
+    prelude::{RwLock, TypeMapKey},
+};
+
+// The timezones users picked with /timezone, used for the times they type in
+pub const TIMEZONES_PATH: &str = "timezones.json";
+
+pub struct UserTimezones;
+
+impl TypeMapKey for UserTimezones {
+    type Value = Arc<RwLock<HashMap<UserId, Tz>>>;
+}
+
+pub fn read_user_timezones() -> HashMap<UserId, Tz> {
+    store::read_from(Path::new(TIMEZONES_PATH)).unwrap_or_default()
+}
+
+fn write_user_timezones(user_timezones: &HashMap<UserId, Tz>) {
+    store::write_to(Path::new(TIMEZONES_PATH), user_timezones);
+}
+
+#[derive(Clone, Copy)]
+pub struct TimezoneCommand;
+
+impl InteractionHandler for TimezoneCommand {
+    fn name(&self) -> &'static str {
+        "timezone"
+    }
+}
+
+#[async_trait]
+impl CommandHandler for TimezoneCommand {
+    async fn invoke(&self, ctx: Context, interaction: ApplicationCommandInteraction) -> Result<()> {
+        let user_id = interaction.user.id;
+        let server_timezone = ctx.config().await.timezone;
+
+        let name = interaction
+            .data
+            .options
+            .iter()
+            .find(|option| option.name == "name")
+            .and_then(|option| match option.resolved.as_ref() {
+                Some(ApplicationCommandInteractionDataOptionValue::String(s)) => Some(s.clone()),
+                _ => None,
+            });
+
+        let user_timezones = ctx
+            .data
+            .read()
+            .await
+            .get::<UserTimezones>()
+            .expect("Error reading user timezones from TypeMap")
+            .clone();
+
+        let content = match name {
+            None => match user_timezones.read().await.get(&user_id) {
+                Some(tz) => format!("Times you type are read in {}", tz.name()),
+                None => format!(
+                    "Times you type are read in the server's timezone, {}",
+                    server_timezone.name()
+                ),
+            },
+            Some(name) if name.eq_ignore_ascii_case("server") => {
+                user_timezones.write().await.remove(&user_id);
+                write_user_timezones(&*user_timezones.read().await);
+                format!(
+                    "Times you type will now be read in the server's timezone, {}",
+                    server_timezone.name()
+                )
+            }
+            Some(name) => match name.parse::<Tz>() {
+                Ok(tz) => {
+                    user_timezones.write().await.insert(user_id, tz);
+                    write_user_timezones(&*user_timezones.read().await);
+                    format!("Times you type will now be read in {}", tz.name())
+                }
+                Err(_) => format!(
+                    "\"{}\" is not a timezone I know! Use a name such as Europe/Berlin or America/New_York",
+                    name
+                ),
+            },
+        };
+
+        interaction_respond_with_private_message(
+            &ctx,
+            &Interaction::ApplicationCommand(interaction),
+            &content,
+        )
+        .await;
+
+        Ok(())
+    }
+
+    fn create_command(
+        self,
+        command: &mut serenity::builder::CreateApplicationCommand,
+    ) -> &mut serenity::builder::CreateApplicationCommand {
+        command
+            .name(self.name())
+            .description("Shows or sets the timezone used for the times you type")
+            .create_option(|option| {
+                option
+                    .kind(ApplicationCommandOptionType::String)
+                    .name("name")
+                    .description(
+                        "Timezone such as Europe/Berlin, or \"server\" to use the server's",
+                    )
+            })
+    }
+}
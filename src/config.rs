@@ -1,6 +1,8 @@
 use std::{fs::File, io::Read, path::Path, sync::Arc};
 
-use serde::Deserialize;
+use chrono::Weekday;
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
 use serenity::{
     model::id::{ApplicationId, ChannelId, GuildId, RoleId, UserId},
     prelude::{RwLock, TypeMapKey},
@@ -18,21 +20,171 @@ pub struct Config {
     pub default_user_role: Option<RoleId>,
     pub default_time: String,
     pub idle_text: String,
-    pub timezone_text: String,
+    #[serde(default = "default_timezone")]
+    pub timezone: Tz,
+    pub timezone_text: Option<String>,
     pub ip_embed: Embed,
     pub default_help: Embed,
     pub help: Vec<HelpPage>,
+    // Users who have every capability
+    #[serde(default)]
     pub admins: Vec<UserId>,
+    #[serde(default)]
+    pub permissions: Permissions,
     pub games: Vec<Game>,
     pub colors: Vec<ColorRole>,
+    #[serde(default)]
+    pub schedules: Vec<Schedule>,
+    #[serde(default = "default_reminders")]
+    pub reminders: Vec<Reminder>,
+    pub temp_voice: Option<TempVoice>,
+    pub auto_end: Option<AutoEnd>,
+    pub no_show_policy: Option<NoShowPolicy>,
+    // Where problems found in the config at startup and on reload are posted
+    pub admin_log_channel: Option<ChannelId>,
 }
 
-#[derive(Deserialize, Clone)]
+// Something only some users are allowed to do
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Capability {
+    // Using /hostgame, /pollgame and /gamevote
+    Host,
+    // Ending, rescheduling and changing the hosts of sessions hosted by somebody else
+    EndAnySession,
+    // Using /allroles
+    ManageRoles,
+    // Using /skipschedule
+    ManageSchedules,
+}
+
+impl Capability {
+    // Whether everyone has the capability while the config doesn't grant it to anybody
+    fn is_open_by_default(self) -> bool {
+        self == Capability::Host
+    }
+}
+
+// The roles and users a capability is granted to
+#[derive(Deserialize, Clone, Default)]
+pub struct Grant {
+    #[serde(default)]
+    pub roles: Vec<RoleId>,
+    #[serde(default)]
+    pub users: Vec<UserId>,
+}
+
+// Who each capability is granted to, when the config says
+#[derive(Deserialize, Clone, Default)]
+pub struct Permissions {
+    pub host: Option<Grant>,
+    pub end_any_session: Option<Grant>,
+    pub manage_roles: Option<Grant>,
+    pub manage_schedules: Option<Grant>,
+}
+
+impl Permissions {
+    pub fn grant(&self, capability: Capability) -> Option<&Grant> {
+        match capability {
+            Capability::Host => self.host.as_ref(),
+            Capability::EndAnySession => self.end_any_session.as_ref(),
+            Capability::ManageRoles => self.manage_roles.as_ref(),
+            Capability::ManageSchedules => self.manage_schedules.as_ref(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
 pub struct Game {
     pub name: String,
     pub channel_id: Option<ChannelId>,
     pub role_id: RoleId,
     pub all_roles_exception: Option<bool>,
+    pub max_players: Option<usize>,
+    // The game's lobby, used instead of the global vc_channel when set
+    pub voice_channel_id: Option<ChannelId>,
+    // Replaces the global reminders for this game's sessions when set
+    pub reminders: Option<Vec<Reminder>>,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct Schedule {
+    pub game: String,
+    pub weekdays: Vec<Weekday>,
+    pub time: String,
+    pub description: Option<String>,
+    pub lead_time: String,
+}
+
+// Where the voice channels made with /hostgame's voice option go, and when they're removed
+#[derive(Deserialize, Clone)]
+pub struct TempVoice {
+    pub category_id: ChannelId,
+    // How long the channel may stay empty once the session started, e.g. "15m"
+    pub empty_timeout: String,
+}
+
+// When running sessions are ended without anybody using /endhost
+#[derive(Deserialize, Clone)]
+pub struct AutoEnd {
+    // How long the session's voice channel may stay empty once it started, e.g. "20m"
+    pub empty_timeout: Option<String>,
+    // How long a session may run for at most, e.g. "6h"
+    pub max_duration: Option<String>,
+}
+
+// Users who said Yes without showing up this many times within the period are put on the
+// waitlist until the session starts when they say Yes
+#[derive(Deserialize, Clone)]
+pub struct NoShowPolicy {
+    pub max_no_shows: usize,
+    // How far back no-shows are counted, e.g. "30d"
+    pub period: String,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Reminder {
+    // When to send the reminder relative to the session's start, e.g. "1h before" or "5m after"
+    pub offset: String,
+    // Message to send, in which {game}, {time} and {pings} are replaced
+    pub message: String,
+    #[serde(default)]
+    pub audience: Audience,
+}
+
+// Who gets pinged by a reminder
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum Audience {
+    // The game's role
+    #[default]
+    Role,
+    // Users who said Yes
+    Yes,
+    // Users who said Yes or Maybe
+    YesMaybe,
+    // Users who said Yes but aren't in the voice channel
+    Late,
+}
+
+// Configs written before the timezone could be set keep working, in UTC
+fn default_timezone() -> Tz {
+    Tz::UTC
+}
+
+// Used when the config has no reminders, these are the reminders the bot always sent
+fn default_reminders() -> Vec<Reminder> {
+    vec![
+        Reminder {
+            offset: "10m before".to_string(),
+            message: "{pings} Session starting soon!".to_string(),
+            audience: Audience::Role,
+        },
+        Reminder {
+            offset: "10m after".to_string(),
+            message: "{pings} you're late, get in the VC!".to_string(),
+            audience: Audience::Late,
+        },
+    ]
 }
 
 #[derive(Deserialize, Clone)]
@@ -46,6 +198,33 @@ impl TypeMapKey for Config {
 }
 
 impl Config {
+    /// Whether the user, who has `roles`, is allowed to do what `capability` covers
+    pub fn allows(&self, user_id: UserId, roles: &[RoleId], capability: Capability) -> bool {
+        if self.admins.contains(&user_id) {
+            return true;
+        }
+
+        match self.permissions.grant(capability) {
+            Some(grant) => {
+                grant.users.contains(&user_id)
+                    || roles.iter().any(|role| grant.roles.contains(role))
+            }
+            None => capability.is_open_by_default(),
+        }
+    }
+
+    /// The voice channel players of `game` are expected to join
+    pub fn voice_channel_for(&self, game: &Game) -> ChannelId {
+        game.voice_channel_id.unwrap_or(self.vc_channel)
+    }
+
+    /// The reminders sent for sessions of `game`
+    pub fn reminders_for(&self, game: &Game) -> Vec<Reminder> {
+        game.reminders
+            .clone()
+            .unwrap_or_else(|| self.reminders.clone())
+    }
+
     pub fn read_from(path: &Path) -> Option<Self> {
         let mut config_file = match File::open(path) {
             Ok(f) => f,
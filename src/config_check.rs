@@ -0,0 +1,698 @@
+use std::{collections::HashMap, fmt, fs, path::Path, sync::Arc};
+
+use chrono::NaiveTime;
+use serenity::{
+    model::{
+        channel::ChannelType,
+        id::{ChannelId, RoleId},
+    },
+    prelude::{RwLock, TypeMap},
+};
+use tracing::{error, info, warn};
+
+use crate::{
+    config::Config,
+    discord::{Discord, DiscordBackend, GuildInfo},
+    embed::Embed,
+    time_parse::{parse_duration, parse_offset},
+};
+
+// Discord doesn't allow more options than this in a select menu
+const MAX_MENU_OPTIONS: usize = 25;
+// Nor labels and descriptions of options longer than this
+const MAX_MENU_TEXT: usize = 100;
+// Nor messages longer than this
+const MAX_MESSAGE_LENGTH: usize = 2000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Severity {
+    // The config can't be used as it is, so a reload keeps the previous one
+    Error,
+    // Part of the config won't work, but the rest of it can still be used
+    Warning,
+}
+
+/// Something wrong with a field of the config
+#[derive(Clone, Debug)]
+pub struct Problem {
+    pub severity: Severity,
+    // Where the field is, e.g. `games[2].role_id`
+    pub field: String,
+    pub message: String,
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        write!(f, "{}: {}: {}", severity, self.field, self.message)
+    }
+}
+
+#[derive(Default)]
+pub struct Report {
+    pub problems: Vec<Problem>,
+}
+
+impl Report {
+    fn error(&mut self, field: impl Into<String>, message: impl Into<String>) {
+        self.push(Severity::Error, field.into(), message.into());
+    }
+
+    fn warning(&mut self, field: impl Into<String>, message: impl Into<String>) {
+        self.push(Severity::Warning, field.into(), message.into());
+    }
+
+    fn push(&mut self, severity: Severity, field: String, message: String) {
+        self.problems.push(Problem {
+            severity,
+            field,
+            message,
+        });
+    }
+
+    pub fn has_errors(&self) -> bool {
+        self.problems
+            .iter()
+            .any(|problem| problem.severity == Severity::Error)
+    }
+
+    pub fn log(&self) {
+        for problem in &self.problems {
+            match problem.severity {
+                Severity::Error => error!("Config {}", problem),
+                Severity::Warning => warn!("Config {}", problem),
+            }
+        }
+    }
+}
+
+/// Checks what can be checked without Discord, i.e. that the config makes sense on its own
+pub fn check(config: &Config) -> Report {
+    let mut report = Report::default();
+
+    if NaiveTime::parse_from_str(&config.default_time, "%H:%M").is_err() {
+        report.error(
+            "default_time",
+            format!("\"{}\" is not a time like \"20:00\"", config.default_time),
+        );
+    }
+
+    // The roles menu has an option for every game
+    let menus = [
+        ("help", config.help.len()),
+        ("colors", config.colors.len()),
+        ("games", config.games.len()),
+    ];
+    for (field, len) in menus {
+        if len > MAX_MENU_OPTIONS {
+            report.error(
+                field,
+                format!(
+                    "has {} entries, but a menu can only show {}",
+                    len, MAX_MENU_OPTIONS
+                ),
+            );
+        }
+    }
+
+    for (i, page) in config.help.iter().enumerate() {
+        check_menu_text(
+            &mut report,
+            format!("help[{}].dropdown_title", i),
+            &page.dropdown_title,
+        );
+        check_menu_text(
+            &mut report,
+            format!("help[{}].dropdown_description", i),
+            &page.dropdown_description,
+        );
+        check_embed(&mut report, format!("help[{}].embed", i), &page.embed);
+    }
+    check_embed(&mut report, "ip_embed".to_string(), &config.ip_embed);
+    check_embed(
+        &mut report,
+        "default_help".to_string(),
+        &config.default_help,
+    );
+
+    // Sessions, schedules and polls find games by their name, so only the first one would be used
+    let mut names = HashMap::new();
+    for (i, game) in config.games.iter().enumerate() {
+        check_menu_text(&mut report, format!("games[{}].name", i), &game.name);
+        match names.get(game.name.as_str()) {
+            Some(first) => report.error(
+                format!("games[{}].name", i),
+                format!("\"{}\" is already the name of games[{}]", game.name, first),
+            ),
+            None => {
+                names.insert(game.name.as_str(), i);
+            }
+        }
+    }
+    for (i, color) in config.colors.iter().enumerate() {
+        check_menu_text(&mut report, format!("colors[{}].name", i), &color.name);
+    }
+
+    // Picking a color removes every other color role, which would take away a game's role too
+    let roles = config
+        .games
+        .iter()
+        .enumerate()
+        .map(|(i, game)| (format!("games[{}].role_id", i), game.role_id))
+        .chain(
+            config
+                .colors
+                .iter()
+                .enumerate()
+                .map(|(i, color)| (format!("colors[{}].role_id", i), color.role_id)),
+        );
+    let mut fields = HashMap::new();
+    for (field, role_id) in roles {
+        match fields.get(&role_id) {
+            Some(first) => report.warning(
+                field,
+                format!("role {} is already used by {}", role_id, first),
+            ),
+            None => {
+                fields.insert(role_id, field);
+            }
+        }
+    }
+
+    for (i, game) in config.games.iter().enumerate() {
+        for (j, reminder) in game.reminders.iter().flatten().enumerate() {
+            check_offset(
+                &mut report,
+                format!("games[{}].reminders[{}].offset", i, j),
+                &reminder.offset,
+            );
+        }
+    }
+
+    for (i, reminder) in config.reminders.iter().enumerate() {
+        check_offset(
+            &mut report,
+            format!("reminders[{}].offset", i),
+            &reminder.offset,
+        );
+    }
+
+    for (i, schedule) in config.schedules.iter().enumerate() {
+        match config.games.iter().find(|game| game.name == schedule.game) {
+            None => report.warning(
+                format!("schedules[{}].game", i),
+                format!("there is no game named \"{}\"", schedule.game),
+            ),
+            Some(game) if game.channel_id.is_none() => report.warning(
+                format!("schedules[{}].game", i),
+                format!("\"{}\" has no channel to host sessions in", schedule.game),
+            ),
+            Some(_) => {}
+        }
+        if NaiveTime::parse_from_str(&schedule.time, "%H:%M").is_err() {
+            report.warning(
+                format!("schedules[{}].time", i),
+                format!("\"{}\" is not a time like \"20:00\"", schedule.time),
+            );
+        }
+        check_duration(
+            &mut report,
+            format!("schedules[{}].lead_time", i),
+            &schedule.lead_time,
+        );
+    }
+
+    if let Some(temp_voice) = &config.temp_voice {
+        check_duration(
+            &mut report,
+            "temp_voice.empty_timeout".to_string(),
+            &temp_voice.empty_timeout,
+        );
+    }
+    if let Some(auto_end) = &config.auto_end {
+        if let Some(empty_timeout) = &auto_end.empty_timeout {
+            check_duration(
+                &mut report,
+                "auto_end.empty_timeout".to_string(),
+                empty_timeout,
+            );
+        }
+        if let Some(max_duration) = &auto_end.max_duration {
+            check_duration(
+                &mut report,
+                "auto_end.max_duration".to_string(),
+                max_duration,
+            );
+        }
+    }
+    if let Some(policy) = &config.no_show_policy {
+        // Everyone has at least 0 no-shows, so every Yes would go to the waitlist
+        if policy.max_no_shows < 1 {
+            report.error(
+                "no_show_policy.max_no_shows",
+                "has to be at least 1, or every Yes would be put on the waitlist",
+            );
+        }
+        check_duration(
+            &mut report,
+            "no_show_policy.period".to_string(),
+            &policy.period,
+        );
+    }
+
+    report
+}
+
+// Labels and descriptions of select menu options
+fn check_menu_text(report: &mut Report, field: String, text: &str) {
+    let len = text.chars().count();
+    if len == 0 {
+        report.error(field, "a menu option can't be empty");
+    } else if len > MAX_MENU_TEXT {
+        report.error(
+            field,
+            format!(
+                "is {} characters long, but a menu option can only show {}",
+                len, MAX_MENU_TEXT
+            ),
+        );
+    }
+}
+
+fn check_embed(report: &mut Report, field: String, embed: &Embed) {
+    for (inner, message) in embed.limit_problems() {
+        let field = if inner.is_empty() {
+            field.clone()
+        } else {
+            format!("{}.{}", field, inner)
+        };
+        report.error(field, message);
+    }
+}
+
+// Durations are only used by optional features, which are skipped when they can't be parsed
+fn check_duration(report: &mut Report, field: String, duration: &str) {
+    if parse_duration(duration).is_none() {
+        report.warning(
+            field,
+            format!("\"{}\" is not a duration like \"15m\"", duration),
+        );
+    }
+}
+
+fn check_offset(report: &mut Report, field: String, offset: &str) {
+    if parse_offset(offset).is_none() {
+        report.warning(
+            field,
+            format!("\"{}\" is not an offset like \"10m before\"", offset),
+        );
+    }
+}
+
+/// Checks the config file at `path` without connecting to Discord, printing what's wrong with it
+///
+/// Returns whether the bot could start with it
+pub fn check_file(path: &Path) -> bool {
+    // The parse error, with its line and column, is logged by read_from
+    let config = match Config::read_from(path) {
+        Some(config) => config,
+        None => return false,
+    };
+    let source = fs::read_to_string(path).unwrap_or_default();
+    let lines = field_lines(&source);
+
+    let report = check(&config);
+    for problem in &report.problems {
+        match locate(&lines, &problem.field) {
+            Some(line) => println!("{}:{}: {}", path.display(), line, problem),
+            None => println!("{}: {}", path.display(), problem),
+        }
+    }
+
+    let errors = report
+        .problems
+        .iter()
+        .filter(|problem| problem.severity == Severity::Error)
+        .count();
+    println!(
+        "{}: {} errors, {} warnings",
+        path.display(),
+        errors,
+        report.problems.len() - errors
+    );
+
+    errors == 0
+}
+
+// The line where each key and table of the TOML source starts, by their path in the config,
+// e.g. `games[1].role_id` for the role_id in the second [[games]] table
+pub fn field_lines(source: &str) -> HashMap<String, usize> {
+    let mut lines = HashMap::new();
+    // How many tables of each array of tables there have been so far
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    let mut table = String::new();
+    let mut in_multiline_string = false;
+
+    for (number, line) in source.lines().enumerate() {
+        let number = number + 1;
+        let line = line.trim();
+
+        let quotes = line.matches("\"\"\"").count() + line.matches("'''").count();
+        if in_multiline_string {
+            in_multiline_string = quotes % 2 == 0;
+            continue;
+        }
+        in_multiline_string = quotes % 2 == 1;
+
+        if let Some(header) = line
+            .strip_prefix("[[")
+            .and_then(|line| line.split("]]").next())
+        {
+            let keys = split_keys(header);
+            let (last, parents) = keys.split_last().unwrap_or((&"", &[]));
+            let base = join_key(&resolve(&counts, parents), last);
+            let count = counts.entry(base.clone()).or_default();
+            *count += 1;
+            table = format!("{}[{}]", base, *count - 1);
+            lines.entry(table.clone()).or_insert(number);
+        } else if let Some(header) = line
+            .strip_prefix('[')
+            .and_then(|line| line.split(']').next())
+        {
+            table = resolve(&counts, &split_keys(header));
+            lines.entry(table.clone()).or_insert(number);
+        } else if let Some((key, _)) = line.split_once('=') {
+            let key = key.trim();
+            let is_key = !key.is_empty()
+                && split_keys(key).iter().all(|key| {
+                    key.chars()
+                        .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+                });
+            if is_key {
+                let field = join_key(&table, &split_keys(key).join("."));
+                lines.entry(field).or_insert(number);
+            }
+        }
+    }
+
+    lines
+}
+
+fn split_keys(keys: &str) -> Vec<&str> {
+    keys.split('.')
+        .map(|key| key.trim().trim_matches('"'))
+        .collect()
+}
+
+fn join_key(table: &str, key: &str) -> String {
+    if table.is_empty() {
+        key.to_string()
+    } else {
+        format!("{}.{}", table, key)
+    }
+}
+
+// The path of a table, where the arrays of tables in it are the last table of each so far
+fn resolve(counts: &HashMap<String, usize>, keys: &[&str]) -> String {
+    let mut path = String::new();
+    for key in keys {
+        path = join_key(&path, key);
+        if let Some(count) = counts.get(&path) {
+            path = format!("{}[{}]", path, count - 1);
+        }
+    }
+    path
+}
+
+// The line of the field, or of the closest field containing it when it's in an inline array or
+// table, or wasn't written out because it has a default
+pub fn locate(lines: &HashMap<String, usize>, field: &str) -> Option<usize> {
+    let mut field = field;
+    loop {
+        if let Some(line) = lines.get(field) {
+            return Some(*line);
+        }
+        field = &field[..field.rfind(['.', '['])?];
+    }
+}
+
+/// Checks that the channels and roles in the config exist in the guild
+///
+/// The ones every session needs are errors, the ones only some features use are warnings
+pub fn check_guild(config: &Config, guild: Option<&GuildInfo>, report: &mut Report) {
+    let guild = match guild {
+        Some(guild) => guild,
+        None => {
+            report.error(
+                "guild_id",
+                format!("the bot is not in guild {}", config.guild_id),
+            );
+            return;
+        }
+    };
+
+    let voice = [ChannelType::Voice, ChannelType::Stage];
+    let text = [ChannelType::Text, ChannelType::News];
+
+    check_channel(
+        report,
+        guild,
+        Severity::Error,
+        "vc_channel".to_string(),
+        config.vc_channel,
+        &voice,
+    );
+    if let Some(role_id) = config.default_user_role {
+        check_role(
+            report,
+            guild,
+            Severity::Warning,
+            "default_user_role".to_string(),
+            role_id,
+        );
+    }
+
+    for (i, game) in config.games.iter().enumerate() {
+        check_role(
+            report,
+            guild,
+            Severity::Error,
+            format!("games[{}].role_id", i),
+            game.role_id,
+        );
+        if let Some(channel_id) = game.channel_id {
+            check_channel(
+                report,
+                guild,
+                Severity::Error,
+                format!("games[{}].channel_id", i),
+                channel_id,
+                &text,
+            );
+        }
+        if let Some(channel_id) = game.voice_channel_id {
+            check_channel(
+                report,
+                guild,
+                Severity::Error,
+                format!("games[{}].voice_channel_id", i),
+                channel_id,
+                &voice,
+            );
+        }
+    }
+
+    for (i, color) in config.colors.iter().enumerate() {
+        check_role(
+            report,
+            guild,
+            Severity::Warning,
+            format!("colors[{}].role_id", i),
+            color.role_id,
+        );
+    }
+
+    let grants = [
+        ("host", &config.permissions.host),
+        ("end_any_session", &config.permissions.end_any_session),
+        ("manage_roles", &config.permissions.manage_roles),
+        ("manage_schedules", &config.permissions.manage_schedules),
+    ];
+    for (name, grant) in grants {
+        for (i, role_id) in grant.iter().flat_map(|grant| &grant.roles).enumerate() {
+            check_role(
+                report,
+                guild,
+                Severity::Warning,
+                format!("permissions.{}.roles[{}]", name, i),
+                *role_id,
+            );
+        }
+    }
+
+    if let Some(temp_voice) = &config.temp_voice {
+        check_channel(
+            report,
+            guild,
+            Severity::Warning,
+            "temp_voice.category_id".to_string(),
+            temp_voice.category_id,
+            &[ChannelType::Category],
+        );
+    }
+    if let Some(channel_id) = config.admin_log_channel {
+        check_channel(
+            report,
+            guild,
+            Severity::Warning,
+            "admin_log_channel".to_string(),
+            channel_id,
+            &text,
+        );
+    }
+}
+
+fn check_channel(
+    report: &mut Report,
+    guild: &GuildInfo,
+    severity: Severity,
+    field: String,
+    channel_id: ChannelId,
+    kinds: &[ChannelType],
+) {
+    match guild.channels.get(&channel_id) {
+        None => report.push(
+            severity,
+            field,
+            format!("there is no channel {} in the guild", channel_id),
+        ),
+        Some(kind) if !kinds.contains(kind) => report.push(
+            severity,
+            field,
+            format!(
+                "<#{}> is a {} channel, not a {} one",
+                channel_id,
+                kind.name(),
+                kinds[0].name()
+            ),
+        ),
+        Some(_) => {}
+    }
+}
+
+fn check_role(
+    report: &mut Report,
+    guild: &GuildInfo,
+    severity: Severity,
+    field: String,
+    role_id: RoleId,
+) {
+    if !guild.roles.contains(&role_id) {
+        report.push(
+            severity,
+            field,
+            format!("there is no role {} in the guild", role_id),
+        );
+    }
+}
+
+/// Runs every check, the guild ones against what Discord knows about the guild
+pub async fn validate(discord: &Arc<dyn Discord>, config: &Config) -> Report {
+    let mut report = check(config);
+    let guild = discord.guild(config.guild_id).await;
+    check_guild(config, guild.as_ref(), &mut report);
+    report
+}
+
+/// Posts the problems in the report to the config's admin log channel, if it has one
+pub async fn post_report(
+    discord: &Arc<dyn Discord>,
+    config: &Config,
+    heading: &str,
+    report: &Report,
+) {
+    let channel_id = match config.admin_log_channel {
+        Some(channel_id) => channel_id,
+        None => return,
+    };
+
+    let mut content = heading.to_string();
+    for problem in &report.problems {
+        let line = format!("\n- {}", problem);
+        if content.len() + line.len() > MAX_MESSAGE_LENGTH {
+            break;
+        }
+        content.push_str(&line);
+    }
+
+    if let Err(why) = discord
+        .send_message(channel_id, |message| message.content(content))
+        .await
+    {
+        warn!("Error posting config report: {}", why);
+    }
+}
+
+/// Swaps `config` in for the current one, unless it has errors, in which case the current one is kept
+pub async fn reload(data: &RwLock<TypeMap>, config: Config) {
+    let discord = data
+        .read()
+        .await
+        .get::<DiscordBackend>()
+        .expect("Discord backend not found")
+        .clone();
+
+    let current = data
+        .read()
+        .await
+        .get::<Config>()
+        .expect("Config not found")
+        .clone();
+    let current = current.read().await;
+
+    // The guild may just not be cached yet, after which cache_ready checks it again.
+    // Only a guild_id which changed has to be one the bot is in
+    let mut report = check(&config);
+    match discord.guild(config.guild_id).await {
+        None if config.guild_id == current.guild_id => report.warning(
+            "guild_id",
+            format!(
+                "guild {} isn't cached yet, its channels and roles weren't checked",
+                config.guild_id
+            ),
+        ),
+        guild => check_guild(&config, guild.as_ref(), &mut report),
+    }
+    report.log();
+
+    if report.has_errors() {
+        error!("The new config has errors, keeping the previous one");
+        // The previous config is the one that's still in use, so its log channel is where people look
+        post_report(
+            &discord,
+            &current,
+            "**config.toml was not reloaded**, fix these and save it again:",
+            &report,
+        )
+        .await;
+        return;
+    }
+
+    if !report.problems.is_empty() {
+        post_report(
+            &discord,
+            &config,
+            "config.toml was reloaded, but some of it won't work:",
+            &report,
+        )
+        .await;
+    }
+
+    data.write()
+        .await
+        .insert::<Config>(Arc::new(RwLock::new(config)));
+    info!("Config changed successfully!");
+}
@@ -1,20 +1,34 @@
-use std::{collections::HashMap, sync::Arc};
+use std::{collections::HashMap, path::Path, sync::Arc};
 
-use chrono::Local;
-use serenity::{async_trait, client::Context, prelude::RwLock};
+use chrono_tz::Tz;
+use serenity::{
+    async_trait,
+    client::Context,
+    model::id::{ChannelId, UserId},
+    prelude::RwLock,
+};
 
 use crate::{
+    commands::timezone::UserTimezones,
     config::Config,
+    discord::{Discord, DiscordBackend},
     interaction_handler::{Handler, InteractionMap},
-    session::Session,
+    session::{SavedSession, Session, SessionMap, SESSIONS_PATH},
+    store,
 };
 
 #[async_trait]
 pub trait ContextExt {
     async fn config(&self) -> Config;
-    async fn session(&self) -> Arc<RwLock<Session>>;
-    async fn is_session_present(&self) -> bool;
-    async fn is_session_started(&self) -> bool;
+    async fn discord(&self) -> Arc<dyn Discord>;
+    async fn user_timezone(&self, user_id: UserId) -> Tz;
+    async fn session(&self, channel_id: ChannelId) -> Option<Arc<RwLock<Session>>>;
+    async fn sessions(&self) -> Vec<Arc<RwLock<Session>>>;
+    async fn is_session_present(&self, channel_id: ChannelId) -> bool;
+    async fn is_session_started(&self, channel_id: ChannelId) -> bool;
+    async fn insert_session(&self, session: Session) -> Result<(), Session>;
+    async fn remove_session(&self, session: &Arc<RwLock<Session>>) -> bool;
+    async fn save_sessions(&self);
     async fn interaction_map(&self) -> HashMap<&'static str, Handler>;
 }
 
@@ -31,21 +45,118 @@ impl ContextExt for Context {
             .clone()
     }
 
-    async fn session(&self) -> Arc<RwLock<Session>> {
+    async fn discord(&self) -> Arc<dyn Discord> {
         self.data
             .read()
             .await
-            .get::<Session>()
-            .expect("Error reading session from TypeMap")
+            .get::<DiscordBackend>()
+            .expect("Error reading Discord backend from TypeMap")
             .clone()
     }
 
-    async fn is_session_present(&self) -> bool {
-        self.data.read().await.get::<Session>().is_some()
+    async fn user_timezone(&self, user_id: UserId) -> Tz {
+        let user_timezone = self
+            .data
+            .read()
+            .await
+            .get::<UserTimezones>()
+            .expect("Error reading user timezones from TypeMap")
+            .read()
+            .await
+            .get(&user_id)
+            .copied();
+
+        match user_timezone {
+            Some(tz) => tz,
+            None => self.config().await.timezone,
+        }
+    }
+
+    async fn session(&self, channel_id: ChannelId) -> Option<Arc<RwLock<Session>>> {
+        self.data
+            .read()
+            .await
+            .get::<SessionMap>()
+            .expect("Error reading sessions from TypeMap")
+            .read()
+            .await
+            .get(&channel_id)
+            .cloned()
     }
 
-    async fn is_session_started(&self) -> bool {
-        self.is_session_present().await && self.session().await.read().await.time <= Local::now()
+    async fn sessions(&self) -> Vec<Arc<RwLock<Session>>> {
+        self.data
+            .read()
+            .await
+            .get::<SessionMap>()
+            .expect("Error reading sessions from TypeMap")
+            .read()
+            .await
+            .values()
+            .cloned()
+            .collect()
+    }
+
+    async fn is_session_present(&self, channel_id: ChannelId) -> bool {
+        self.session(channel_id).await.is_some()
+    }
+
+    async fn is_session_started(&self, channel_id: ChannelId) -> bool {
+        match self.session(channel_id).await {
+            Some(session) => session.read().await.is_started(),
+            None => false,
+        }
+    }
+
+    // The check and the insert happen under one lock, so two sessions started at once can't both
+    // take the channel. The one which lost is given back, so its caller can clean up after it
+    async fn insert_session(&self, session: Session) -> Result<(), Session> {
+        let data = self.data.read().await;
+        let mut sessions = data
+            .get::<SessionMap>()
+            .expect("Error reading sessions from TypeMap")
+            .write()
+            .await;
+
+        if sessions.contains_key(&session.channel_id()) {
+            return Err(session);
+        }
+        sessions.insert(session.channel_id(), Arc::new(RwLock::new(session)));
+        Ok(())
+    }
+
+    // Only removes the session if it is still the one running in its channel,
+    // so a session which was ended twice doesn't take a newer one with it
+    async fn remove_session(&self, session: &Arc<RwLock<Session>>) -> bool {
+        let channel_id = session.read().await.channel_id();
+        let data = self.data.read().await;
+        let mut sessions = data
+            .get::<SessionMap>()
+            .expect("Error reading sessions from TypeMap")
+            .write()
+            .await;
+
+        match sessions.get(&channel_id) {
+            Some(current) if Arc::ptr_eq(current, session) => {
+                sessions.remove(&channel_id);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    async fn save_sessions(&self) {
+        let mut saved: Vec<SavedSession> = vec![];
+        for session in self.sessions().await {
+            saved.push(session.read().await.to_saved());
+        }
+
+        let path = Path::new(SESSIONS_PATH);
+        if saved.is_empty() {
+            store::remove(path);
+        } else {
+            store::write_to(path, &saved);
+        }
     }
 
     async fn interaction_map(&self) -> HashMap<&'static str, Handler> {
@@ -0,0 +1,515 @@
+use std::{collections::HashMap, sync::Arc};
+
+use reqwest::{Client, Method, Response};
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use serenity::{
+    async_trait,
+    builder::{
+        CreateChannel, CreateInteractionResponse, CreateInteractionResponseFollowup, CreateMessage,
+        EditMessage,
+    },
+    cache::Cache,
+    http::{AttachmentType, Http, HttpError, StatusCode},
+    model::{
+        channel::ChannelType,
+        id::{ChannelId, GuildId, InteractionId, MessageId, RoleId, UserId},
+    },
+    prelude::TypeMapKey,
+    utils::{hashmap_to_json_map, Colour},
+    Result,
+};
+
+pub type JsonMap = Map<String, Value>;
+
+/// What the bot needs to know about a member of the guild
+#[derive(Clone, Debug, Default)]
+pub struct MemberInfo {
+    // Their nickname, or their username if they don't have one
+    pub name: String,
+    pub avatar_url: String,
+    pub roles: Vec<RoleId>,
+}
+
+/// The channels and roles of the guild, which the config is checked against
+#[derive(Clone, Debug, Default)]
+pub struct GuildInfo {
+    pub channels: HashMap<ChannelId, ChannelType>,
+    pub roles: Vec<RoleId>,
+}
+
+/// A file attached to a message, as its name and contents
+pub type File = (String, Vec<u8>);
+
+// Discord's error codes for a channel or message which doesn't exist
+pub const UNKNOWN_CHANNEL: isize = 10003;
+pub const UNKNOWN_MESSAGE: isize = 10008;
+
+/// Whether Discord refused a request because what it was about is gone,
+/// rather than failing in a way which may go away on its own
+pub fn is_unknown(why: &serenity::Error) -> bool {
+    match why {
+        serenity::Error::Http(why) => match why.as_ref() {
+            HttpError::UnsuccessfulRequest(response) => {
+                response.status_code == StatusCode::NOT_FOUND
+                    || matches!(response.error.code, UNKNOWN_CHANNEL | UNKNOWN_MESSAGE)
+            }
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+/// The Discord operations the bot's features go through, so they can be replaced by a fake in tests.
+/// Messages and responses are passed as the JSON serenity's builders produce
+#[async_trait]
+pub trait Discord: Send + Sync {
+    async fn send_message_json(&self, channel_id: ChannelId, message: JsonMap)
+        -> Result<MessageId>;
+
+    async fn edit_message_json(
+        &self,
+        channel_id: ChannelId,
+        message_id: MessageId,
+        message: JsonMap,
+    ) -> Result<()>;
+
+    async fn delete_message(&self, channel_id: ChannelId, message_id: MessageId) -> Result<()>;
+
+    async fn pin_message(&self, channel_id: ChannelId, message_id: MessageId) -> Result<()>;
+
+    async fn unpin_message(&self, channel_id: ChannelId, message_id: MessageId) -> Result<()>;
+
+    async fn send_dm_json(&self, user_id: UserId, message: JsonMap) -> Result<()>;
+
+    async fn member(&self, guild_id: GuildId, user_id: UserId) -> Result<MemberInfo>;
+
+    async fn add_role(&self, guild_id: GuildId, user_id: UserId, role_id: RoleId) -> Result<()>;
+
+    async fn remove_role(&self, guild_id: GuildId, user_id: UserId, role_id: RoleId) -> Result<()>;
+
+    /// The users in the voice channel, or None if the guild isn't known yet
+    async fn voice_members(&self, guild_id: GuildId, channel_id: ChannelId) -> Option<Vec<UserId>>;
+
+    /// The guild's channels and roles, or None if the guild isn't known yet
+    async fn guild(&self, guild_id: GuildId) -> Option<GuildInfo>;
+
+    /// The colour of one of the guild's roles, or None if the role isn't known
+    async fn role_colour(&self, guild_id: GuildId, role_id: RoleId) -> Option<Colour>;
+
+    /// The id of the bot's own user
+    async fn current_user_id(&self) -> UserId;
+
+    async fn create_channel_json(&self, guild_id: GuildId, channel: JsonMap) -> Result<ChannelId>;
+
+    async fn delete_channel(&self, channel_id: ChannelId) -> Result<()>;
+
+    /// Creates a guild scheduled event, returning its id
+    async fn create_scheduled_event_json(&self, guild_id: GuildId, event: JsonMap) -> Result<u64>;
+
+    async fn edit_scheduled_event_json(
+        &self,
+        guild_id: GuildId,
+        event_id: u64,
+        event: JsonMap,
+    ) -> Result<()>;
+
+    /// One page of the users who marked the scheduled event as "Interested", in order of their id.
+    /// The page starts after the user `after`, or at the first user when it's None
+    async fn scheduled_event_users(
+        &self,
+        guild_id: GuildId,
+        event_id: u64,
+        after: Option<UserId>,
+    ) -> Result<Vec<UserId>>;
+
+    async fn create_interaction_response_json(
+        &self,
+        interaction_id: InteractionId,
+        token: &str,
+        response: JsonMap,
+    ) -> Result<()>;
+
+    async fn create_followup_message_json(
+        &self,
+        token: &str,
+        message: JsonMap,
+        files: Vec<File>,
+    ) -> Result<()>;
+}
+
+// The builder versions of the operations, which is what the features use
+impl dyn Discord {
+    pub async fn send_message<'a, F>(&self, channel_id: ChannelId, f: F) -> Result<MessageId>
+    where
+        for<'b> F: FnOnce(&'b mut CreateMessage<'a>) -> &'b mut CreateMessage<'a>,
+    {
+        let message = {
+            let mut message = CreateMessage::default();
+            f(&mut message);
+            hashmap_to_json_map(message.0)
+        };
+        self.send_message_json(channel_id, message).await
+    }
+
+    pub async fn edit_message<F>(
+        &self,
+        channel_id: ChannelId,
+        message_id: MessageId,
+        f: F,
+    ) -> Result<()>
+    where
+        F: FnOnce(&mut EditMessage) -> &mut EditMessage,
+    {
+        let message = {
+            let mut message = EditMessage::default();
+            f(&mut message);
+            hashmap_to_json_map(message.0)
+        };
+        self.edit_message_json(channel_id, message_id, message)
+            .await
+    }
+
+    pub async fn create_channel<F>(&self, guild_id: GuildId, f: F) -> Result<ChannelId>
+    where
+        F: FnOnce(&mut CreateChannel) -> &mut CreateChannel,
+    {
+        let channel = {
+            let mut channel = CreateChannel::default();
+            f(&mut channel);
+            hashmap_to_json_map(channel.0)
+        };
+        self.create_channel_json(guild_id, channel).await
+    }
+
+    pub async fn send_dm<'a, F>(&self, user_id: UserId, f: F) -> Result<()>
+    where
+        for<'b> F: FnOnce(&'b mut CreateMessage<'a>) -> &'b mut CreateMessage<'a>,
+    {
+        let message = {
+            let mut message = CreateMessage::default();
+            f(&mut message);
+            hashmap_to_json_map(message.0)
+        };
+        self.send_dm_json(user_id, message).await
+    }
+
+    pub async fn create_interaction_response<F>(
+        &self,
+        interaction_id: InteractionId,
+        token: &str,
+        f: F,
+    ) -> Result<()>
+    where
+        F: FnOnce(&mut CreateInteractionResponse) -> &mut CreateInteractionResponse,
+    {
+        let response = {
+            let mut response = CreateInteractionResponse::default();
+            f(&mut response);
+            hashmap_to_json_map(response.0)
+        };
+        self.create_interaction_response_json(interaction_id, token, response)
+            .await
+    }
+
+    pub async fn create_followup_message<'a, F>(&self, token: &str, f: F) -> Result<()>
+    where
+        for<'b> F: FnOnce(
+            &'b mut CreateInteractionResponseFollowup<'a>,
+        ) -> &'b mut CreateInteractionResponseFollowup<'a>,
+    {
+        let (message, files) = {
+            let mut followup = CreateInteractionResponseFollowup::default();
+            f(&mut followup);
+            let files = followup
+                .1
+                .into_iter()
+                .filter_map(|file| match file {
+                    AttachmentType::Bytes { data, filename } => Some((filename, data.into_owned())),
+                    _ => None,
+                })
+                .collect();
+            (hashmap_to_json_map(followup.0), files)
+        };
+        self.create_followup_message_json(token, message, files)
+            .await
+    }
+}
+
+// The Discord the bot talks to, the real one unless a test put a fake in its place
+pub struct DiscordBackend;
+
+impl TypeMapKey for DiscordBackend {
+    type Value = Arc<dyn Discord>;
+}
+
+// serenity doesn't know about guild scheduled events yet, so they're managed through the API directly
+const API_BASE: &str = "https://discord.com/api/v9";
+
+// The most users Discord returns per request for a scheduled event
+const EVENT_USERS_PAGE: usize = 100;
+
+#[derive(Deserialize)]
+struct CreatedEvent {
+    id: String,
+}
+
+#[derive(Deserialize)]
+struct EventUser {
+    user: EventUserId,
+}
+
+#[derive(Deserialize)]
+struct EventUserId {
+    id: String,
+}
+
+/// Talks to the real Discord through serenity
+pub struct SerenityDiscord {
+    http: Arc<Http>,
+    cache: Arc<Cache>,
+    // For the requests serenity can't make, shared so its connections are reused
+    client: Client,
+}
+
+impl SerenityDiscord {
+    pub fn new(http: Arc<Http>, cache: Arc<Cache>) -> Self {
+        Self {
+            http,
+            cache,
+            client: Client::new(),
+        }
+    }
+
+    async fn scheduled_event_request(
+        &self,
+        method: Method,
+        guild_id: GuildId,
+        path: &str,
+        body: Option<JsonMap>,
+    ) -> Result<Response> {
+        let url = format!("{}/guilds/{}/scheduled-events{}", API_BASE, guild_id, path);
+        let mut request = self
+            .client
+            .request(method, &url)
+            .header("Authorization", &self.http.token);
+        if let Some(body) = body {
+            request = request.json(&body);
+        }
+
+        let response = request.send().await?;
+        if response.status().is_success() {
+            Ok(response)
+        } else {
+            Err(HttpError::from_response(response).await.into())
+        }
+    }
+}
+
+#[async_trait]
+impl Discord for SerenityDiscord {
+    async fn send_message_json(
+        &self,
+        channel_id: ChannelId,
+        message: JsonMap,
+    ) -> Result<MessageId> {
+        self.http
+            .send_message(channel_id.0, &Value::Object(message))
+            .await
+            .map(|message| message.id)
+    }
+
+    async fn edit_message_json(
+        &self,
+        channel_id: ChannelId,
+        message_id: MessageId,
+        message: JsonMap,
+    ) -> Result<()> {
+        self.http
+            .edit_message(channel_id.0, message_id.0, &Value::Object(message))
+            .await
+            .map(|_| ())
+    }
+
+    async fn delete_message(&self, channel_id: ChannelId, message_id: MessageId) -> Result<()> {
+        self.http.delete_message(channel_id.0, message_id.0).await
+    }
+
+    async fn pin_message(&self, channel_id: ChannelId, message_id: MessageId) -> Result<()> {
+        self.http.pin_message(channel_id.0, message_id.0).await
+    }
+
+    async fn unpin_message(&self, channel_id: ChannelId, message_id: MessageId) -> Result<()> {
+        self.http.unpin_message(channel_id.0, message_id.0).await
+    }
+
+    async fn send_dm_json(&self, user_id: UserId, message: JsonMap) -> Result<()> {
+        let dm = user_id.create_dm_channel(&self.http).await?;
+        self.http
+            .send_message(dm.id.0, &Value::Object(message))
+            .await
+            .map(|_| ())
+    }
+
+    async fn member(&self, guild_id: GuildId, user_id: UserId) -> Result<MemberInfo> {
+        let member = guild_id
+            .member((&self.cache, self.http.as_ref()), user_id)
+            .await?;
+
+        Ok(MemberInfo {
+            name: member.display_name().into_owned(),
+            avatar_url: member.user.face(),
+            roles: member.roles,
+        })
+    }
+
+    async fn add_role(&self, guild_id: GuildId, user_id: UserId, role_id: RoleId) -> Result<()> {
+        self.http
+            .add_member_role(guild_id.0, user_id.0, role_id.0)
+            .await
+    }
+
+    async fn remove_role(&self, guild_id: GuildId, user_id: UserId, role_id: RoleId) -> Result<()> {
+        self.http
+            .remove_member_role(guild_id.0, user_id.0, role_id.0)
+            .await
+    }
+
+    async fn voice_members(&self, guild_id: GuildId, channel_id: ChannelId) -> Option<Vec<UserId>> {
+        self.cache
+            .guild_field(guild_id, |guild| {
+                guild
+                    .voice_states
+                    .values()
+                    .filter(|state| state.channel_id == Some(channel_id))
+                    .map(|state| state.user_id)
+                    .collect()
+            })
+            .await
+    }
+
+    async fn guild(&self, guild_id: GuildId) -> Option<GuildInfo> {
+        self.cache
+            .guild_field(guild_id, |guild| GuildInfo {
+                channels: guild
+                    .channels
+                    .iter()
+                    .map(|(channel_id, channel)| (*channel_id, channel.kind))
+                    .collect(),
+                roles: guild.roles.keys().copied().collect(),
+            })
+            .await
+    }
+
+    async fn role_colour(&self, guild_id: GuildId, role_id: RoleId) -> Option<Colour> {
+        self.cache
+            .role(guild_id, role_id)
+            .await
+            .map(|role| role.colour)
+    }
+
+    async fn current_user_id(&self) -> UserId {
+        self.cache.current_user_id().await
+    }
+
+    async fn create_channel_json(&self, guild_id: GuildId, channel: JsonMap) -> Result<ChannelId> {
+        self.http
+            .create_channel(guild_id.0, &channel)
+            .await
+            .map(|channel| channel.id)
+    }
+
+    async fn delete_channel(&self, channel_id: ChannelId) -> Result<()> {
+        self.http.delete_channel(channel_id.0).await.map(|_| ())
+    }
+
+    async fn create_scheduled_event_json(&self, guild_id: GuildId, event: JsonMap) -> Result<u64> {
+        let event = self
+            .scheduled_event_request(Method::POST, guild_id, "", Some(event))
+            .await?
+            .json::<CreatedEvent>()
+            .await?;
+        event
+            .id
+            .parse()
+            .map_err(|_| serenity::Error::Other("Scheduled event id is not a number"))
+    }
+
+    async fn edit_scheduled_event_json(
+        &self,
+        guild_id: GuildId,
+        event_id: u64,
+        event: JsonMap,
+    ) -> Result<()> {
+        self.scheduled_event_request(
+            Method::PATCH,
+            guild_id,
+            &format!("/{}", event_id),
+            Some(event),
+        )
+        .await
+        .map(|_| ())
+    }
+
+    async fn scheduled_event_users(
+        &self,
+        guild_id: GuildId,
+        event_id: u64,
+        after: Option<UserId>,
+    ) -> Result<Vec<UserId>> {
+        let mut path = format!("/{}/users?limit={}", event_id, EVENT_USERS_PAGE);
+        if let Some(after) = after {
+            path += &format!("&after={}", after);
+        }
+
+        let users = self
+            .scheduled_event_request(Method::GET, guild_id, &path, None)
+            .await?
+            .json::<Vec<EventUser>>()
+            .await?;
+        Ok(users
+            .iter()
+            .filter_map(|user| user.user.id.parse().ok().map(UserId))
+            .collect())
+    }
+
+    async fn create_interaction_response_json(
+        &self,
+        interaction_id: InteractionId,
+        token: &str,
+        response: JsonMap,
+    ) -> Result<()> {
+        self.http
+            .create_interaction_response(interaction_id.0, token, &Value::Object(response))
+            .await
+    }
+
+    async fn create_followup_message_json(
+        &self,
+        token: &str,
+        message: JsonMap,
+        files: Vec<File>,
+    ) -> Result<()> {
+        if files.is_empty() {
+            return self
+                .http
+                .create_followup_message(token, &Value::Object(message))
+                .await
+                .map(|_| ());
+        }
+
+        // serenity drops the files of followup messages, but a followup is a message sent through
+        // the application's webhook, which can carry files
+        let files = files
+            .into_iter()
+            .map(|(filename, data)| AttachmentType::Bytes {
+                data: data.into(),
+                filename,
+            })
+            .collect::<Vec<_>>();
+        self.http
+            .execute_webhook_with_files(self.http.application_id, token, true, files, message)
+            .await
+            .map(|_| ())
+    }
+}
@@ -1,6 +1,14 @@
 use serde::Deserialize;
 use serenity::{builder::CreateEmbed, utils::Colour};
 
+// Discord rejects embeds which go over any of these, counted in characters
+const MAX_TITLE: usize = 256;
+const MAX_DESCRIPTION: usize = 4096;
+const MAX_FIELDS: usize = 25;
+const MAX_FIELD_NAME: usize = 256;
+const MAX_FIELD_VALUE: usize = 1024;
+const MAX_TOTAL: usize = 6000;
+
 #[derive(Deserialize, Clone)]
 pub struct Embed {
     title: String,
@@ -36,6 +44,66 @@ impl Embed {
 
         embed
     }
+
+    /// The parts of the embed Discord would reject, as the field they're in and what's wrong with it
+    ///
+    /// An empty field means the problem is with the embed as a whole
+    pub fn limit_problems(&self) -> Vec<(String, String)> {
+        let mut problems = vec![];
+        let mut check = |field: String, text: &str, max: usize| {
+            let len = text.chars().count();
+            if len > max {
+                problems.push((
+                    field,
+                    format!(
+                        "is {} characters long, but at most {} are allowed",
+                        len, max
+                    ),
+                ));
+            }
+            len
+        };
+
+        let mut total = check("title".to_string(), &self.title, MAX_TITLE);
+        if let Some(description) = &self.description {
+            total += check("description".to_string(), description, MAX_DESCRIPTION);
+        }
+        for (i, section) in self.sections.iter().enumerate() {
+            total += check(
+                format!("sections[{}].title", i),
+                &section.title,
+                MAX_FIELD_NAME,
+            );
+            total += check(
+                format!("sections[{}].content", i),
+                &section.content,
+                MAX_FIELD_VALUE,
+            );
+        }
+
+        if self.sections.len() > MAX_FIELDS {
+            problems.push((
+                "sections".to_string(),
+                format!(
+                    "has {} entries, but an embed can only have {}",
+                    self.sections.len(),
+                    MAX_FIELDS
+                ),
+            ));
+        }
+        if total > MAX_TOTAL {
+            // The limit is on the whole embed, rather than on one of its fields
+            problems.push((
+                String::new(),
+                format!(
+                    "is {} characters long in total, but at most {} are allowed",
+                    total, MAX_TOTAL
+                ),
+            ));
+        }
+
+        problems
+    }
 }
 
 impl Section {
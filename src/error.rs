@@ -0,0 +1,60 @@
+use std::fmt;
+
+use serenity::model::id::ChannelId;
+
+/// Why a handler couldn't do what it was asked to
+#[derive(Debug)]
+pub enum Error {
+    // A request to Discord failed
+    Discord(serenity::Error),
+    // The interaction lacks something Discord always sends, or has a value the bot never offered,
+    // which only happens when it was forged or sent from outside the guild
+    InvalidInteraction(&'static str),
+    // A session was started in a channel which doesn't belong to any game
+    NotGameChannel(ChannelId),
+    // Another session took the channel while this one was being set up
+    SessionRunning(ChannelId),
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+impl Error {
+    /// What to tell the user whose interaction failed, without the details which only matter in the logs
+    pub fn user_message(&self) -> String {
+        match self {
+            Error::Discord(_) => {
+                "Something went wrong while talking to Discord, please try again!".to_string()
+            }
+            Error::InvalidInteraction(_) => "I couldn't make sense of that request!".to_string(),
+            Error::NotGameChannel(channel_id) => {
+                format!("<#{}> is not a game channel!", channel_id)
+            }
+            Error::SessionRunning(channel_id) => {
+                format!("There is already a session running in <#{}>!", channel_id)
+            }
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Discord(why) => write!(f, "Discord request failed: {}", why),
+            Error::InvalidInteraction(why) => write!(f, "Invalid interaction: {}", why),
+            Error::NotGameChannel(channel_id) => {
+                write!(f, "Channel {} is not a game channel", channel_id)
+            }
+            Error::SessionRunning(channel_id) => {
+                write!(f, "Channel {} already has a session", channel_id)
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<serenity::Error> for Error {
+    fn from(why: serenity::Error) -> Self {
+        Error::Discord(why)
+    }
+}
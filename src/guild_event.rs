@@ -0,0 +1,148 @@
+use std::sync::Arc;
+
+use chrono::{DateTime, Utc};
+use serde_json::json;
+use serenity::{
+    client::Context,
+    model::id::{ChannelId, UserId},
+    prelude::RwLock,
+};
+use tracing::warn;
+
+use crate::{
+    announcement::schedule_announcement_update,
+    context_ext::ContextExt,
+    discord::JsonMap,
+    session::{Session, UserState},
+};
+
+// https://discord.com/developers/docs/resources/guild-scheduled-event
+const PRIVACY_GUILD_ONLY: u8 = 2;
+const ENTITY_VOICE: u8 = 2;
+
+#[derive(Clone, Copy)]
+pub enum EventStatus {
+    Active = 2,
+    Completed = 3,
+    Canceled = 4,
+}
+
+/// Creates a guild scheduled event for a session in `voice_channel`, returning its id
+pub async fn create_event(
+    ctx: &Context,
+    name: &str,
+    description: &str,
+    time: DateTime<Utc>,
+    voice_channel: ChannelId,
+) -> Option<u64> {
+    let mut event = JsonMap::new();
+    event.insert("name".to_string(), json!(name));
+    event.insert("privacy_level".to_string(), json!(PRIVACY_GUILD_ONLY));
+    event.insert("scheduled_start_time".to_string(), json!(time.to_rfc3339()));
+    event.insert("entity_type".to_string(), json!(ENTITY_VOICE));
+    event.insert("channel_id".to_string(), json!(voice_channel.to_string()));
+    if !description.is_empty() {
+        event.insert("description".to_string(), json!(description));
+    }
+
+    let guild_id = ctx.config().await.guild_id;
+    match ctx
+        .discord()
+        .await
+        .create_scheduled_event_json(guild_id, event)
+        .await
+    {
+        Ok(event_id) => Some(event_id),
+        Err(why) => {
+            warn!("Error creating scheduled event: {}", why);
+            None
+        }
+    }
+}
+
+async fn edit_event(ctx: &Context, event_id: u64, event: JsonMap) {
+    let guild_id = ctx.config().await.guild_id;
+    if let Err(why) = ctx
+        .discord()
+        .await
+        .edit_scheduled_event_json(guild_id, event_id, event)
+        .await
+    {
+        warn!("Error editing scheduled event {}: {}", event_id, why);
+    }
+}
+
+pub async fn move_event(ctx: &Context, event_id: u64, time: DateTime<Utc>) {
+    let mut event = JsonMap::new();
+    event.insert("scheduled_start_time".to_string(), json!(time.to_rfc3339()));
+    edit_event(ctx, event_id, event).await;
+}
+
+pub async fn set_event_status(ctx: &Context, event_id: u64, status: EventStatus) {
+    let mut event = JsonMap::new();
+    event.insert("status".to_string(), json!(status as u8));
+    edit_event(ctx, event_id, event).await;
+}
+
+// Every interested user, a page at a time
+async fn interested_users(ctx: &Context, event_id: u64) -> Option<Vec<UserId>> {
+    let guild_id = ctx.config().await.guild_id;
+    let discord = ctx.discord().await;
+
+    let mut users: Vec<UserId> = vec![];
+    loop {
+        let page = match discord
+            .scheduled_event_users(guild_id, event_id, users.last().copied())
+            .await
+        {
+            Ok(page) => page,
+            Err(why) => {
+                warn!("Error reading scheduled event users: {}", why);
+                return None;
+            }
+        };
+        if page.is_empty() {
+            return Some(users);
+        }
+        users.extend(page);
+    }
+}
+
+/// Adds the users who marked the session's event as "Interested" as Maybe,
+/// unless they already responded to the session themselves
+pub async fn import_interested(ctx: &Context, session: &Arc<RwLock<Session>>) {
+    let event_id = match session.read().await.event_id {
+        Some(event_id) => event_id,
+        None => return,
+    };
+
+    let users = match interested_users(ctx, event_id).await {
+        Some(users) => users,
+        None => return,
+    };
+
+    let mut changed = false;
+    {
+        let mut session = session.write().await;
+        for user_id in users {
+            if !session.users.contains_key(&user_id) && !session.waitlist.contains(&user_id) {
+                session.respond(user_id, UserState::May, false);
+                changed = true;
+            }
+        }
+    }
+
+    if changed {
+        ctx.save_sessions().await;
+        schedule_announcement_update(ctx, session.clone()).await;
+    }
+}
+
+/// Imports the interested users of every session which hasn't started yet
+pub async fn import_all_interested(ctx: &Context) {
+    for session in ctx.sessions().await {
+        if !session.read().await.is_started() {
+            import_interested(ctx, &session).await;
+        }
+    }
+}
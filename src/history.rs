@@ -0,0 +1,109 @@
+use std::{collections::HashMap, path::Path, sync::Arc};
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use serenity::{
+    model::id::UserId,
+    prelude::{RwLock, TypeMapKey},
+};
+
+use crate::{
+    session::{Session, UserState, VoiceStint},
+    store,
+};
+
+// Every session which was ended or cancelled, kept for /stats
+pub const HISTORY_PATH: &str = "history.json";
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ArchivedSession {
+    pub game: String,
+    pub host: UserId,
+    pub time: DateTime<Utc>,
+    pub ended_at: DateTime<Utc>,
+    // False if the session was cancelled before it started
+    pub started: bool,
+    pub users: HashMap<UserId, UserState>,
+    pub attendance: HashMap<UserId, Vec<VoiceStint>>,
+}
+
+impl ArchivedSession {
+    pub fn from_session(session: &Session, ended_at: DateTime<Utc>) -> Self {
+        Self {
+            game: session.game.name.clone(),
+            host: session.host,
+            time: session.time,
+            ended_at,
+            started: session.time <= ended_at,
+            users: session.users.clone(),
+            attendance: session.attendance.clone(),
+        }
+    }
+
+    /// How long the session ran for, zero if it was cancelled
+    pub fn duration(&self) -> Duration {
+        if self.started {
+            self.ended_at - self.time
+        } else {
+            Duration::zero()
+        }
+    }
+
+    pub fn attended(&self, user_id: UserId) -> bool {
+        self.attendance.contains_key(&user_id)
+    }
+
+    pub fn time_in_voice(&self, user_id: UserId) -> Duration {
+        self.attendance
+            .get(&user_id)
+            .map(|stints| {
+                stints.iter().fold(Duration::zero(), |total, stint| {
+                    total + stint.duration(self.ended_at)
+                })
+            })
+            .unwrap_or_else(Duration::zero)
+    }
+
+    pub fn said_yes(&self, user_id: UserId) -> bool {
+        self.users.get(&user_id) == Some(&UserState::Will)
+    }
+}
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct History {
+    sessions: Vec<ArchivedSession>,
+}
+
+impl TypeMapKey for History {
+    type Value = Arc<RwLock<History>>;
+}
+
+impl History {
+    pub fn read() -> Self {
+        store::read_from(Path::new(HISTORY_PATH)).unwrap_or_default()
+    }
+
+    pub fn archive(&mut self, session: ArchivedSession) {
+        self.sessions.push(session);
+        store::write_to(Path::new(HISTORY_PATH), self);
+    }
+
+    /// The archived sessions which were planned for `since` or later
+    pub fn since(&self, since: Option<DateTime<Utc>>) -> Vec<&ArchivedSession> {
+        self.sessions
+            .iter()
+            .filter(|session| since.is_none_or(|since| session.time >= since))
+            .collect()
+    }
+
+    /// How many of the sessions the user said Yes to since `since` they showed up to,
+    /// as (showed up, said Yes)
+    pub fn reliability(&self, user_id: UserId, since: Option<DateTime<Utc>>) -> (usize, usize) {
+        self.since(since)
+            .into_iter()
+            .filter(|session| session.started && session.said_yes(user_id))
+            .fold((0, 0), |(showed_up, said_yes), session| {
+                (showed_up + session.attended(user_id) as usize, said_yes + 1)
+            })
+    }
+}
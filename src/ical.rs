@@ -0,0 +1,82 @@
+use chrono::{DateTime, Duration, Utc};
+
+use crate::clock;
+
+// Lines longer than this many octets have to be folded
+const MAX_LINE_LENGTH: usize = 75;
+
+pub struct CalendarEvent {
+    // Stays the same for the same session, so calendars update the event instead of adding it twice
+    pub uid: String,
+    pub summary: String,
+    pub description: String,
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    // How long before the start each alarm goes off
+    pub alarms: Vec<Duration>,
+}
+
+fn format_time(time: DateTime<Utc>) -> String {
+    time.format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+fn escape_text(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace('\n', "\\n")
+}
+
+// Splits a content line into lines of at most 75 octets, continued lines starting with a space
+fn fold_line(line: &str) -> String {
+    let mut folded = String::new();
+    let mut length = 0;
+
+    for c in line.chars() {
+        if length + c.len_utf8() > MAX_LINE_LENGTH {
+            folded.push_str("\r\n ");
+            length = 1;
+        }
+        folded.push(c);
+        length += c.len_utf8();
+    }
+
+    folded + "\r\n"
+}
+
+/// Renders the events as an RFC 5545 calendar, ready to be saved as an .ics file
+pub fn to_ics(events: &[CalendarEvent]) -> String {
+    let now = format_time(clock::now());
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        "PRODID:-//session-bot-rs//EN".to_string(),
+        "CALSCALE:GREGORIAN".to_string(),
+        "METHOD:PUBLISH".to_string(),
+    ];
+
+    for event in events {
+        lines.push("BEGIN:VEVENT".to_string());
+        lines.push(format!("UID:{}", event.uid));
+        lines.push(format!("DTSTAMP:{}", now));
+        lines.push(format!("DTSTART:{}", format_time(event.start)));
+        lines.push(format!("DTEND:{}", format_time(event.end)));
+        lines.push(format!("SUMMARY:{}", escape_text(&event.summary)));
+        if !event.description.is_empty() {
+            lines.push(format!("DESCRIPTION:{}", escape_text(&event.description)));
+        }
+
+        for alarm in &event.alarms {
+            lines.push("BEGIN:VALARM".to_string());
+            lines.push("ACTION:DISPLAY".to_string());
+            lines.push(format!("DESCRIPTION:{}", escape_text(&event.summary)));
+            lines.push(format!("TRIGGER:-PT{}M", alarm.num_minutes()));
+            lines.push("END:VALARM".to_string());
+        }
+
+        lines.push("END:VEVENT".to_string());
+    }
+
+    lines.push("END:VCALENDAR".to_string());
+    lines.iter().map(|line| fold_line(line)).collect()
+}
@@ -3,7 +3,8 @@ use serenity::{
     builder::CreateApplicationCommand,
     client::Context,
     model::{
-        id::GuildId,
+        guild::Member,
+        id::{GuildId, UserId},
         interactions::{
             application_command::{ApplicationCommand, ApplicationCommandInteraction},
             message_component::MessageComponentInteraction,
@@ -13,13 +14,20 @@ use serenity::{
 };
 use std::{collections::HashMap, sync::Arc};
 
+use crate::{config::Capability, context_ext::ContextExt, error::Result};
+
 pub trait InteractionHandler {
     fn name(&self) -> &'static str;
+
+    /// What users need to be allowed to do to use this, checked before it is invoked
+    fn capability(&self) -> Option<Capability> {
+        None
+    }
 }
 
 #[async_trait]
 pub trait CommandHandler: InteractionHandler {
-    async fn invoke(&self, ctx: Context, interaction: ApplicationCommandInteraction);
+    async fn invoke(&self, ctx: Context, interaction: ApplicationCommandInteraction) -> Result<()>;
 
     fn create_command(
         self,
@@ -29,7 +37,7 @@ pub trait CommandHandler: InteractionHandler {
 
 #[async_trait]
 pub trait MessageHandler: InteractionHandler {
-    async fn invoke(&self, ctx: Context, interaction: MessageComponentInteraction);
+    async fn invoke(&self, ctx: Context, interaction: MessageComponentInteraction) -> Result<()>;
 }
 
 #[derive(Clone)]
@@ -45,6 +53,7 @@ impl TypeMapKey for InteractionMap {
     type Value = Arc<RwLock<HashMap<&'static str, Handler>>>;
 }
 
+#[allow(dead_code)]
 pub async fn register_global_command<T>(ctx: &Context, handler: T)
 where
     T: CommandHandler + Send + Sync + Copy + 'static,
@@ -71,14 +80,13 @@ where
     guild_id
         .create_application_command(&ctx.http, |f| handler.create_command(f))
         .await
-        .expect(
-            format!(
+        .unwrap_or_else(|_| {
+            panic!(
                 "There was an error creating guild #{} {} command",
                 guild_id,
                 handler.name()
             )
-            .as_str(),
-        );
+        });
 
     register_handler(ctx, Handler::Command(Arc::new(handler))).await;
 }
@@ -98,3 +106,21 @@ pub async fn register_handler(ctx: &Context, handler: Handler) {
         .await
         .insert(name, handler);
 }
+
+/// Whether the user may use a handler which needs `capability`
+pub async fn is_allowed(
+    ctx: &Context,
+    capability: Option<Capability>,
+    user_id: UserId,
+    member: Option<&Member>,
+) -> bool {
+    let capability = match capability {
+        Some(capability) => capability,
+        None => return true,
+    };
+    let roles = member
+        .map(|member| member.roles.as_slice())
+        .unwrap_or_default();
+
+    ctx.config().await.allows(user_id, roles, capability)
+}
@@ -1,14 +1,31 @@
+mod announcement;
+mod attendance;
+mod clock;
 mod commands;
 mod config;
+mod config_check;
 mod context_ext;
+mod discord;
 mod embed;
+mod error;
+mod guild_event;
+mod history;
+mod ical;
 mod interaction_handler;
+mod poll;
+mod scheduler;
 mod session;
+mod store;
+#[cfg(test)]
+mod tests;
+mod time_parse;
+mod timer;
+mod voice;
 
 use hotwatch::Hotwatch;
 use serenity::{
     async_trait,
-    model::{gateway::Ready, interactions::Interaction},
+    model::{gateway::Ready, id::GuildId, interactions::Interaction, voice::VoiceState},
     prelude::*,
 };
 use std::{collections::HashMap, path::Path, sync::Arc};
@@ -18,18 +35,33 @@ use tracing::{error, info, warn};
 use crate::{
     commands::{
         allroles::AllRoles,
+        calendar::{CalendarButton, CalendarCommand},
+        cohost::{CoHost, TransferHost},
         colors::{self, ColorsCommand},
         endhost::{self, EndHost},
+        gamevote::{self, GameVoteCommand},
         help::{self, Help},
         hostgame::{self, HostGame},
         ip::Ip,
+        pollgame::{self, PollGame},
         prelude::*,
+        reschedule::Reschedule,
         roles::{self, RolesCommand},
+        skipschedule::SkipSchedule,
+        stats::Stats,
         status::Status,
+        timezone::{self, TimezoneCommand},
     },
     config::Config,
     context_ext::ContextExt,
-    interaction_handler::{register_guild_command, register_handler, Handler, InteractionMap},
+    discord::{DiscordBackend, SerenityDiscord},
+    history::History,
+    interaction_handler::{
+        is_allowed, register_guild_command, register_handler, Handler, InteractionMap,
+    },
+    poll::Polls,
+    scheduler::ScheduleState,
+    session::SessionMap,
 };
 
 struct ClientHandler;
@@ -43,7 +75,29 @@ impl EventHandler for ClientHandler {
             Interaction::ApplicationCommand(interaction) => {
                 let name = interaction.data.name.clone();
                 if let Some(Handler::Command(command)) = map.get(name.as_str()) {
-                    command.invoke(ctx.clone(), interaction).await;
+                    let user_id = interaction.user.id;
+                    if is_allowed(
+                        &ctx,
+                        command.capability(),
+                        user_id,
+                        interaction.member.as_ref(),
+                    )
+                    .await
+                    {
+                        let (id, token) = (interaction.id, interaction.token.clone());
+                        if let Err(why) = command.invoke(ctx.clone(), interaction).await {
+                            warn!("Error running /{}: {}", name, why);
+                            interaction_respond_with_error(&ctx, id, &token, &why.user_message())
+                                .await;
+                        }
+                    } else {
+                        interaction_respond_with_private_message(
+                            &ctx,
+                            &Interaction::ApplicationCommand(interaction),
+                            "You do not have permissions to use this command!",
+                        )
+                        .await;
+                    }
                 } else {
                     warn!("Slash command not found in map: {}", name);
                 }
@@ -51,17 +105,66 @@ impl EventHandler for ClientHandler {
             Interaction::MessageComponent(interaction) => {
                 let name = interaction.data.custom_id.clone();
                 if let Some(Handler::Message(message_handler)) = map.get(name.as_str()) {
-                    message_handler.invoke(ctx.clone(), interaction).await;
+                    let user_id = interaction.user.id;
+                    let member = interaction.member.as_ref();
+                    if is_allowed(&ctx, message_handler.capability(), user_id, member).await {
+                        let (id, token) = (interaction.id, interaction.token.clone());
+                        if let Err(why) = message_handler.invoke(ctx.clone(), interaction).await {
+                            warn!("Error handling {}: {}", name, why);
+                            interaction_respond_with_error(&ctx, id, &token, &why.user_message())
+                                .await;
+                        }
+                    } else {
+                        interaction_respond_with_private_message(
+                            &ctx,
+                            &Interaction::MessageComponent(interaction),
+                            "You do not have permissions to do this!",
+                        )
+                        .await;
+                    }
                 } else {
                     warn!("Message handler not found in map: {}", name);
                 }
             }
-            Interaction::Ping(_) => {
+            Interaction::Ping(_) | Interaction::Autocomplete(_) => {
                 error!("Error: interaction kind not supported: {:?}", interaction)
             }
         }
     }
 
+    async fn voice_state_update(
+        &self,
+        ctx: Context,
+        _: Option<GuildId>,
+        _old: Option<VoiceState>,
+        new: VoiceState,
+    ) {
+        attendance::record_voice_state(&ctx, &new).await;
+    }
+
+    // Voice states are only known once the guild is cached, which can be after the sessions were restored
+    async fn cache_ready(&self, ctx: Context, _guilds: Vec<GuildId>) {
+        // So are the guild's channels and roles, which the config refers to
+        let config = ctx.config().await;
+        let discord = ctx.discord().await;
+        let report = config_check::validate(&discord, &config).await;
+        report.log();
+        if !report.problems.is_empty() {
+            config_check::post_report(
+                &discord,
+                &config,
+                "Some of config.toml won't work:",
+                &report,
+            )
+            .await;
+        }
+
+        for session in ctx.sessions().await {
+            attendance::sync_attendance(&ctx, &session).await;
+        }
+        ctx.save_sessions().await;
+    }
+
     async fn ready(&self, ctx: Context, ready: Ready) {
         info!("Connected as user: {}", ready.user.name);
 
@@ -81,16 +184,33 @@ impl EventHandler for ClientHandler {
         register_guild_command(&ctx, guild_id, RolesCommand).await;
         register_guild_command(&ctx, guild_id, AllRoles).await;
         register_guild_command(&ctx, guild_id, ColorsCommand).await;
+        register_guild_command(&ctx, guild_id, TimezoneCommand).await;
+        register_guild_command(&ctx, guild_id, SkipSchedule).await;
+        register_guild_command(&ctx, guild_id, Reschedule).await;
+        register_guild_command(&ctx, guild_id, Stats).await;
+        register_guild_command(&ctx, guild_id, CalendarCommand).await;
+        register_guild_command(&ctx, guild_id, PollGame).await;
+        register_guild_command(&ctx, guild_id, GameVoteCommand).await;
+        register_guild_command(&ctx, guild_id, CoHost).await;
+        register_guild_command(&ctx, guild_id, TransferHost).await;
 
+        register_handler(&ctx, Handler::Message(Arc::new(hostgame::ButtonConfirm))).await;
+        register_handler(&ctx, Handler::Message(Arc::new(hostgame::ButtonCancel))).await;
         register_handler(&ctx, Handler::Message(Arc::new(hostgame::ButtonYes))).await;
         register_handler(&ctx, Handler::Message(Arc::new(hostgame::ButtonMaybe))).await;
         register_handler(&ctx, Handler::Message(Arc::new(hostgame::ButtonNo))).await;
+        register_handler(&ctx, Handler::Message(Arc::new(CalendarButton))).await;
         register_handler(&ctx, Handler::Message(Arc::new(endhost::ButtonYes))).await;
         register_handler(&ctx, Handler::Message(Arc::new(endhost::ButtonNo))).await;
         register_handler(&ctx, Handler::Message(Arc::new(help::MenuHandler))).await;
         register_handler(&ctx, Handler::Message(Arc::new(roles::MenuHandler))).await;
         register_handler(&ctx, Handler::Message(Arc::new(colors::MenuHandler))).await;
+        register_handler(&ctx, Handler::Message(Arc::new(pollgame::MenuHandler))).await;
+        register_handler(&ctx, Handler::Message(Arc::new(gamevote::MenuHandler))).await;
+        register_handler(&ctx, Handler::Message(Arc::new(gamevote::ButtonClose))).await;
 
+        hostgame::restore_sessions(&ctx).await;
+        scheduler::start(&ctx).await;
         update_bot_status(&ctx).await;
         info!("All commands have been added successfully!");
     }
@@ -100,7 +220,22 @@ impl EventHandler for ClientHandler {
 async fn main() {
     tracing_subscriber::fmt::init();
 
+    // `session-bot-rs check-config [path]` checks a config without starting the bot
+    let args: Vec<String> = std::env::args().collect();
+    if args.get(1).map(String::as_str) == Some("check-config") {
+        let path = args.get(2).map_or("config.toml", String::as_str);
+        let is_usable = config_check::check_file(Path::new(path));
+        std::process::exit(if is_usable { 0 } else { 1 });
+    }
+
     let config = Config::read_from(Path::new("config.toml")).expect("Could not open config.toml");
+    // The guild can only be checked once it's cached, see cache_ready
+    let report = config_check::check(&config);
+    report.log();
+    if report.has_errors() {
+        error!("config.toml has errors, fix them and restart the bot");
+        std::process::exit(1);
+    }
     let token = config.discord_token.clone();
 
     let mut client = Client::builder(token)
@@ -109,10 +244,35 @@ async fn main() {
         .await
         .expect("Error creating client");
     let data = client.data.clone();
+    let discord = SerenityDiscord::new(
+        client.cache_and_http.http.clone(),
+        client.cache_and_http.cache.clone(),
+    );
 
     data.write()
         .await
         .insert::<Config>(Arc::new(RwLock::new(config)));
+    data.write()
+        .await
+        .insert::<DiscordBackend>(Arc::new(discord));
+    data.write()
+        .await
+        .insert::<SessionMap>(Arc::new(RwLock::new(HashMap::new())));
+    data.write()
+        .await
+        .insert::<hostgame::PendingSessionMap>(HashMap::new());
+    data.write()
+        .await
+        .insert::<timezone::UserTimezones>(Arc::new(RwLock::new(timezone::read_user_timezones())));
+    data.write()
+        .await
+        .insert::<ScheduleState>(Arc::new(RwLock::new(ScheduleState::read())));
+    data.write()
+        .await
+        .insert::<History>(Arc::new(RwLock::new(History::read())));
+    data.write()
+        .await
+        .insert::<Polls>(Arc::new(RwLock::new(Polls::read())));
 
     let handle = Handle::current();
     let mut hotwatch = Hotwatch::new().expect("Hotwatch failed to initialize!");
@@ -121,14 +281,7 @@ async fn main() {
             if let hotwatch::Event::Write(_) = event {
                 if let Some(config) = Config::read_from(Path::new("config.toml")) {
                     info!("Config change detected");
-
-                    handle.block_on(async {
-                        data.write()
-                            .await
-                            .insert::<Config>(Arc::new(RwLock::new(config)));
-                    });
-
-                    info!("Config changed successfully!");
+                    handle.block_on(config_check::reload(&data, config));
                 }
             }
         })
@@ -0,0 +1,205 @@
+use std::{collections::HashMap, path::Path, sync::Arc};
+
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+use serenity::{
+    model::id::{ChannelId, MessageId, UserId},
+    prelude::{RwLock, TypeMapKey},
+};
+
+use crate::store;
+
+// Polls which are still open are saved here so they survive restarts
+pub const POLLS_PATH: &str = "polls.json";
+
+/// A vote on when to hold a session, which becomes a session at the winning time once it closes
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Poll {
+    pub channel_id: ChannelId,
+    pub message_id: MessageId,
+    pub host: UserId,
+    pub times: Vec<DateTime<Utc>>,
+    // The host's timezone, which the times are shown in on the select menu
+    pub timezone: Tz,
+    pub closes_at: DateTime<Utc>,
+    pub description: String,
+    pub max_players: Option<usize>,
+    // The indices into `times` each user can make it at
+    pub votes: HashMap<UserId, Vec<usize>>,
+}
+
+impl Poll {
+    pub fn vote_count(&self, idx: usize) -> usize {
+        self.votes
+            .values()
+            .filter(|votes| votes.contains(&idx))
+            .count()
+    }
+
+    pub fn voters(&self, idx: usize) -> Vec<UserId> {
+        self.votes
+            .iter()
+            .filter(|(_, votes)| votes.contains(&idx))
+            .map(|(user_id, _)| *user_id)
+            .collect()
+    }
+
+    /// The time with the most votes which hasn't passed yet, the earliest one on a tie.
+    /// None if nobody voted for such a time
+    pub fn winner(&self, now: DateTime<Utc>) -> Option<usize> {
+        (0..self.times.len())
+            .filter(|idx| self.times[*idx] > now && self.vote_count(*idx) > 0)
+            .max_by_key(|idx| (self.vote_count(*idx), std::cmp::Reverse(self.times[*idx])))
+    }
+}
+
+/// A vote on which game to play, which becomes a session of the winning game once the host closes it
+#[derive(Serialize, Deserialize, Clone)]
+pub struct GameVote {
+    pub channel_id: ChannelId,
+    pub message_id: MessageId,
+    pub host: UserId,
+    // Resolved when the vote is closed, so relative times like "in 30m" count from then
+    pub time: Option<String>,
+    pub description: String,
+    // The name of the game each user voted for
+    pub votes: HashMap<UserId, String>,
+}
+
+impl GameVote {
+    pub fn vote_count(&self, game: &str) -> usize {
+        self.votes.values().filter(|vote| *vote == game).count()
+    }
+
+    pub fn voters(&self, game: &str) -> Vec<UserId> {
+        self.votes
+            .iter()
+            .filter(|(_, vote)| *vote == game)
+            .map(|(user_id, _)| *user_id)
+            .collect()
+    }
+
+    /// The game with the most votes out of `games`, the first one on a tie. None if nobody voted
+    pub fn winner<'a>(&self, games: &[&'a str]) -> Option<&'a str> {
+        games
+            .iter()
+            .copied()
+            .filter(|game| self.vote_count(game) > 0)
+            .rev()
+            .max_by_key(|game| self.vote_count(game))
+    }
+}
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct Polls {
+    polls: Vec<Poll>,
+    #[serde(default)]
+    game_votes: Vec<GameVote>,
+}
+
+impl TypeMapKey for Polls {
+    type Value = Arc<RwLock<Polls>>;
+}
+
+impl Polls {
+    pub fn read() -> Self {
+        store::read_from(Path::new(POLLS_PATH)).unwrap_or_default()
+    }
+
+    fn save(&self) {
+        store::write_to(Path::new(POLLS_PATH), self);
+    }
+
+    pub fn is_open_in(&self, channel_id: ChannelId) -> bool {
+        self.polls.iter().any(|poll| poll.channel_id == channel_id)
+    }
+
+    pub fn open(&mut self, poll: Poll) {
+        self.polls.push(poll);
+        self.save();
+    }
+
+    pub fn poll(&self, message_id: MessageId) -> Option<Poll> {
+        self.polls
+            .iter()
+            .find(|poll| poll.message_id == message_id)
+            .cloned()
+    }
+
+    /// Replaces the user's votes on the poll posted as `message_id`, returning the updated poll
+    pub fn vote(
+        &mut self,
+        message_id: MessageId,
+        user_id: UserId,
+        votes: Vec<usize>,
+    ) -> Option<Poll> {
+        let poll = self
+            .polls
+            .iter_mut()
+            .find(|poll| poll.message_id == message_id)?;
+
+        if votes.is_empty() {
+            poll.votes.remove(&user_id);
+        } else {
+            poll.votes.insert(user_id, votes);
+        }
+
+        let poll = poll.clone();
+        self.save();
+        Some(poll)
+    }
+
+    /// Removes and returns the polls which should be closed by `now`
+    pub fn take_due(&mut self, now: DateTime<Utc>) -> Vec<Poll> {
+        let (due, open) = self.polls.drain(..).partition(|poll| poll.closes_at <= now);
+        self.polls = open;
+
+        if !due.is_empty() {
+            self.save();
+        }
+        due
+    }
+
+    pub fn open_game_vote(&mut self, game_vote: GameVote) {
+        self.game_votes.push(game_vote);
+        self.save();
+    }
+
+    pub fn game_vote(&self, message_id: MessageId) -> Option<GameVote> {
+        self.game_votes
+            .iter()
+            .find(|game_vote| game_vote.message_id == message_id)
+            .cloned()
+    }
+
+    /// Replaces the user's vote on the game vote posted as `message_id`, returning the updated vote
+    pub fn vote_for_game(
+        &mut self,
+        message_id: MessageId,
+        user_id: UserId,
+        game: String,
+    ) -> Option<GameVote> {
+        let game_vote = self
+            .game_votes
+            .iter_mut()
+            .find(|game_vote| game_vote.message_id == message_id)?;
+        game_vote.votes.insert(user_id, game);
+
+        let game_vote = game_vote.clone();
+        self.save();
+        Some(game_vote)
+    }
+
+    /// Removes and returns the game vote posted as `message_id`, if it is still open
+    pub fn close_game_vote(&mut self, message_id: MessageId) -> Option<GameVote> {
+        let index = self
+            .game_votes
+            .iter()
+            .position(|game_vote| game_vote.message_id == message_id)?;
+
+        let game_vote = self.game_votes.remove(index);
+        self.save();
+        Some(game_vote)
+    }
+}
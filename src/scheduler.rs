@@ -0,0 +1,261 @@
+use std::{path::Path, sync::Arc, time::Duration};
+
+use chrono::{DateTime, Datelike, NaiveTime, TimeZone, Utc};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+use serenity::{
+    client::Context,
+    prelude::{RwLock, TypeMapKey},
+};
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+use crate::{
+    clock,
+    commands::{
+        endhost::end_overdue_sessions,
+        hostgame::{start_session, SessionRequest},
+        pollgame::close_due_polls,
+    },
+    config::Schedule,
+    context_ext::ContextExt,
+    guild_event::import_all_interested,
+    store,
+    time_parse::parse_duration,
+    voice::check_voice_channels,
+};
+
+// Occurrences which were already posted or skipped are saved here so they are never posted twice
+pub const SCHEDULES_PATH: &str = "schedules.json";
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct Occurrence {
+    pub game: String,
+    pub time: DateTime<Utc>,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct ScheduleState {
+    handled: Vec<Occurrence>,
+}
+
+impl TypeMapKey for ScheduleState {
+    type Value = Arc<RwLock<ScheduleState>>;
+}
+
+impl ScheduleState {
+    pub fn read() -> Self {
+        store::read_from(Path::new(SCHEDULES_PATH)).unwrap_or_default()
+    }
+
+    pub fn is_handled(&self, occurrence: &Occurrence) -> bool {
+        self.handled.contains(occurrence)
+    }
+
+    /// Marks an occurrence as posted or skipped, forgetting the ones which are already over
+    pub fn mark_handled(&mut self, occurrence: Occurrence) {
+        let now = clock::now();
+        self.handled.retain(|handled| handled.time > now);
+        self.handled.push(occurrence);
+        store::write_to(Path::new(SCHEDULES_PATH), self);
+    }
+}
+
+pub struct SchedulerHandle;
+
+impl TypeMapKey for SchedulerHandle {
+    type Value = Vec<JoinHandle<()>>;
+}
+
+// The jobs the bot runs in the background, each on its own interval
+#[derive(Clone, Copy)]
+enum Job {
+    // Posts the scheduled sessions whose lead time has come
+    PostScheduledSessions,
+    // Turns the polls whose deadline has passed into sessions
+    CloseDuePolls,
+    // Ends sessions and deletes created channels whose voice channel stayed empty for too long
+    CheckVoiceChannels,
+    // Adds the users interested in the sessions' scheduled events as Maybe
+    ImportInterested,
+    // Ends the sessions which ran for longer than the configured max_duration
+    EndOverdueSessions,
+}
+
+impl Job {
+    const ALL: [Job; 5] = [
+        Job::PostScheduledSessions,
+        Job::CloseDuePolls,
+        Job::CheckVoiceChannels,
+        Job::ImportInterested,
+        Job::EndOverdueSessions,
+    ];
+
+    fn interval(self) -> Duration {
+        match self {
+            Job::PostScheduledSessions | Job::CloseDuePolls | Job::CheckVoiceChannels => {
+                Duration::from_secs(30)
+            }
+            // Takes a request per page of interested users of every session's event
+            Job::ImportInterested => Duration::from_secs(5 * 60),
+            Job::EndOverdueSessions => Duration::from_secs(60),
+        }
+    }
+
+    async fn run(self, ctx: &Context) {
+        match self {
+            Job::PostScheduledSessions => post_scheduled_sessions(ctx).await,
+            Job::CloseDuePolls => close_due_polls(ctx).await,
+            Job::CheckVoiceChannels => check_voice_channels(ctx).await,
+            Job::ImportInterested => import_all_interested(ctx).await,
+            Job::EndOverdueSessions => end_overdue_sessions(ctx).await,
+        }
+    }
+}
+
+/// The next time `schedule` takes place at or after `now`, or None if its time can't be parsed
+pub fn next_occurrence(schedule: &Schedule, now: DateTime<Tz>) -> Option<DateTime<Utc>> {
+    let time = NaiveTime::parse_from_str(&schedule.time, "%H:%M").ok()?;
+    let today = now.naive_local().date();
+
+    (0..=7)
+        .map(|days| today + chrono::Duration::days(days))
+        .filter(|date| schedule.weekdays.contains(&date.weekday()))
+        .filter_map(|date| {
+            now.timezone()
+                .from_local_datetime(&date.and_time(time))
+                .earliest()
+        })
+        .find(|time| *time >= now)
+        .map(|time| time.with_timezone(&Utc))
+}
+
+/// Starts the background jobs, unless they are already running from a previous ready event
+pub async fn start(ctx: &Context) {
+    if ctx.data.read().await.contains_key::<SchedulerHandle>() {
+        return;
+    }
+
+    let handles = Job::ALL
+        .iter()
+        .map(|&job| {
+            let ctx = ctx.clone();
+            tokio::task::spawn(async move {
+                let mut interval = tokio::time::interval(job.interval());
+                loop {
+                    interval.tick().await;
+                    job.run(&ctx).await;
+                }
+            })
+        })
+        .collect();
+
+    ctx.data.write().await.insert::<SchedulerHandle>(handles);
+}
+
+/// Posts the scheduled sessions whose lead time has come. Each occurrence is handled once, a session
+/// which can't be posted is reported instead of being retried
+///
+/// The config is read again every time, so changes made while the bot runs are picked up
+pub async fn post_scheduled_sessions(ctx: &Context) {
+    let config = ctx.config().await;
+    let now = clock::now();
+    let state = ctx
+        .data
+        .read()
+        .await
+        .get::<ScheduleState>()
+        .expect("Error reading schedule state from TypeMap")
+        .clone();
+
+    for schedule in &config.schedules {
+        let channel_id = match config
+            .games
+            .iter()
+            .find(|game| game.name == schedule.game)
+            .and_then(|game| game.channel_id)
+        {
+            Some(channel_id) => channel_id,
+            None => continue,
+        };
+
+        let time = match next_occurrence(schedule, now.with_timezone(&config.timezone)) {
+            Some(time) => time,
+            None => continue,
+        };
+
+        let lead_time = match parse_duration(&schedule.lead_time) {
+            Some(lead_time) => lead_time,
+            None => continue,
+        };
+
+        let occurrence = Occurrence {
+            game: schedule.game.clone(),
+            time,
+        };
+
+        if now < time - lead_time || state.read().await.is_handled(&occurrence) {
+            continue;
+        }
+
+        if ctx.is_session_present(channel_id).await {
+            report_skipped(
+                ctx,
+                &format!(
+                    "Skipped the scheduled {} session at <t:{}:F>, a session is already running in <#{}>",
+                    schedule.game,
+                    time.timestamp(),
+                    channel_id
+                ),
+            )
+            .await;
+            state.write().await.mark_handled(occurrence);
+            continue;
+        }
+
+        let request = SessionRequest {
+            host: ctx.discord().await.current_user_id().await,
+            time,
+            description: schedule.description.clone().unwrap_or_default(),
+            max_players: None,
+            temp_voice: false,
+            scheduled_event: false,
+        };
+
+        match start_session(ctx, channel_id, request).await {
+            Ok(()) => info!("Posted scheduled {} session", schedule.game),
+            Err(why) => {
+                report_skipped(
+                    ctx,
+                    &format!(
+                        "Could not post the scheduled {} session at <t:{}:F>: {}",
+                        schedule.game,
+                        time.timestamp(),
+                        why
+                    ),
+                )
+                .await
+            }
+        }
+
+        state.write().await.mark_handled(occurrence);
+    }
+}
+
+// Occurrences are never retried, so the admins are told about the ones which didn't get posted
+async fn report_skipped(ctx: &Context, content: &str) {
+    warn!("{}", content);
+
+    let channel_id = match ctx.config().await.admin_log_channel {
+        Some(channel_id) => channel_id,
+        None => return,
+    };
+    if let Err(why) = ctx
+        .discord()
+        .await
+        .send_message(channel_id, |message| message.content(content))
+        .await
+    {
+        warn!("Error posting skipped scheduled session: {}", why);
+    }
+}
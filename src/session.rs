@@ -1,49 +1,333 @@
 use std::{collections::HashMap, sync::Arc};
 
-use chrono::{DateTime, Local};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
 use serenity::{
-    model::id::{MessageId, UserId},
+    model::id::{ChannelId, MessageId, UserId},
     prelude::{RwLock, TypeMapKey},
 };
 use tokio::task::JoinHandle;
 
-use crate::config::Game;
+use crate::{
+    clock,
+    config::{Config, Game},
+};
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+// The running sessions are saved here so they can be picked back up after a restart
+pub const SESSIONS_PATH: &str = "sessions.json";
+
+// Before sessions could run in several channels at once, the one running session was saved here
+pub const LEGACY_SESSION_PATH: &str = "session.json";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum UserState {
     Will,
     May,
     Wont,
 }
 
+// What happened to the user who responded Yes
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Rsvp {
+    Joined,
+    // The session is full, the user is at this (1-based) position of the waitlist
+    Waitlisted(usize),
+    // The user missed too many sessions lately, they're on the waitlist until the session starts
+    Probation,
+}
+
+// A stretch of time a user spent in the session's voice channel, `left` is None while they're still in it
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct VoiceStint {
+    pub joined: DateTime<Utc>,
+    pub left: Option<DateTime<Utc>>,
+}
+
+impl VoiceStint {
+    /// How long the stint lasted, up to `now` if the user is still in the channel
+    pub fn duration(&self, now: DateTime<Utc>) -> Duration {
+        self.left.unwrap_or(now) - self.joined
+    }
+}
+
 pub struct Session {
     pub game: Game,
     pub users: HashMap<UserId, UserState>,
-    pub time: DateTime<Local>,
+    pub time: DateTime<Utc>,
+    pub description: String,
     pub handle: JoinHandle<()>,
     pub message_id: MessageId,
     pub host: UserId,
+    // Users who can manage the session just like the host
+    pub cohosts: Vec<UserId>,
+    pub max_players: Option<usize>,
+    // Users who said Yes while the session was full, in the order they did so
+    pub waitlist: Vec<UserId>,
+    // Users on the waitlist because of the no-show policy, who can only be moved off it once the session starts
+    pub probation: Vec<UserId>,
+    // When each user was in the voice channel since the session started
+    pub attendance: HashMap<UserId, Vec<VoiceStint>>,
+    // The voice channel created for this session, if any
+    pub temp_voice_channel: Option<ChannelId>,
+    // The guild scheduled event mirroring this session, if any
+    pub event_id: Option<u64>,
+    // Since when the session's voice channel has been empty, while the session is running
+    pub voice_empty_since: Option<DateTime<Utc>>,
+    // Set while an edit of the announcement message is waiting to be sent
+    pub announcement_update_pending: bool,
 }
 
-impl TypeMapKey for Session {
-    type Value = Arc<RwLock<Session>>;
+// Everything in a Session except the task handle, which has to be re-spawned on load
+#[derive(Serialize, Deserialize)]
+pub struct SavedSession {
+    pub game: Game,
+    pub users: HashMap<UserId, UserState>,
+    pub time: DateTime<Utc>,
+    #[serde(default)]
+    pub description: String,
+    pub message_id: MessageId,
+    pub host: UserId,
+    #[serde(default)]
+    pub cohosts: Vec<UserId>,
+    #[serde(default)]
+    pub max_players: Option<usize>,
+    #[serde(default)]
+    pub waitlist: Vec<UserId>,
+    #[serde(default)]
+    pub probation: Vec<UserId>,
+    #[serde(default)]
+    pub attendance: HashMap<UserId, Vec<VoiceStint>>,
+    #[serde(default)]
+    pub temp_voice_channel: Option<ChannelId>,
+    #[serde(default)]
+    pub event_id: Option<u64>,
+}
+
+// Every running session, keyed by the game channel it was hosted in
+pub struct SessionMap;
+
+impl TypeMapKey for SessionMap {
+    type Value = Arc<RwLock<HashMap<ChannelId, Arc<RwLock<Session>>>>>;
 }
 
 impl Session {
     pub fn new(
         game: Game,
         handle: JoinHandle<()>,
-        time: DateTime<Local>,
+        time: DateTime<Utc>,
+        description: String,
         message_id: MessageId,
         host: UserId,
+        max_players: Option<usize>,
     ) -> Self {
         Self {
             game,
             users: HashMap::new(),
             time,
+            description,
             handle,
             message_id,
             host,
+            cohosts: vec![],
+            max_players,
+            waitlist: vec![],
+            probation: vec![],
+            attendance: HashMap::new(),
+            temp_voice_channel: None,
+            event_id: None,
+            voice_empty_since: None,
+            announcement_update_pending: false,
+        }
+    }
+
+    pub fn from_saved(saved: SavedSession, handle: JoinHandle<()>) -> Self {
+        Self {
+            game: saved.game,
+            users: saved.users,
+            time: saved.time,
+            description: saved.description,
+            handle,
+            message_id: saved.message_id,
+            host: saved.host,
+            cohosts: saved.cohosts,
+            max_players: saved.max_players,
+            waitlist: saved.waitlist,
+            probation: saved.probation,
+            attendance: saved.attendance,
+            temp_voice_channel: saved.temp_voice_channel,
+            event_id: saved.event_id,
+            voice_empty_since: None,
+            announcement_update_pending: false,
         }
     }
+
+    pub fn channel_id(&self) -> ChannelId {
+        // UNWRAP SAFETY: You can not host a game which has a ChannelId set to None
+        self.game.channel_id.unwrap()
+    }
+
+    /// The voice channel the session is played in
+    pub fn voice_channel(&self, config: &Config) -> ChannelId {
+        self.temp_voice_channel
+            .unwrap_or_else(|| config.voice_channel_for(&self.game))
+    }
+
+    pub fn is_started(&self) -> bool {
+        self.time <= clock::now()
+    }
+
+    pub fn to_saved(&self) -> SavedSession {
+        SavedSession {
+            game: self.game.clone(),
+            users: self.users.clone(),
+            time: self.time,
+            description: self.description.clone(),
+            message_id: self.message_id,
+            host: self.host,
+            cohosts: self.cohosts.clone(),
+            max_players: self.max_players,
+            waitlist: self.waitlist.clone(),
+            probation: self.probation.clone(),
+            attendance: self.attendance.clone(),
+            temp_voice_channel: self.temp_voice_channel,
+            event_id: self.event_id,
+        }
+    }
+
+    /// Whether the user is the host or one of the co-hosts
+    pub fn is_host(&self, user_id: UserId) -> bool {
+        self.host == user_id || self.cohosts.contains(&user_id)
+    }
+
+    /// The host followed by the co-hosts
+    pub fn hosts(&self) -> Vec<UserId> {
+        let mut hosts = vec![self.host];
+        hosts.extend(&self.cohosts);
+        hosts
+    }
+
+    /// Hands the session over to `user_id`, who stops being a co-host if they were one.
+    /// The previous host stays on as a co-host
+    pub fn transfer_host(&mut self, user_id: UserId) {
+        self.cohosts.retain(|cohost| *cohost != user_id);
+        let previous = std::mem::replace(&mut self.host, user_id);
+        self.cohosts.push(previous);
+    }
+
+    pub fn player_count(&self) -> usize {
+        self.users
+            .values()
+            .filter(|state| **state == UserState::Will)
+            .count()
+    }
+
+    pub fn is_full(&self) -> bool {
+        match self.max_players {
+            Some(max_players) => self.player_count() >= max_players,
+            None => false,
+        }
+    }
+
+    /// Records a user's response. Yes responders go to the back of the waitlist while the
+    /// session is full, or until it starts if they're `on_probation`, and whenever a spot opens
+    /// up the front of the waitlist takes it.
+    /// Returns what happened to a Yes responder, along with the users who were promoted
+    pub fn respond(
+        &mut self,
+        user_id: UserId,
+        state: UserState,
+        on_probation: bool,
+    ) -> (Option<Rsvp>, Vec<UserId>) {
+        let rsvp = if state != UserState::Will {
+            self.waitlist.retain(|waiting| *waiting != user_id);
+            self.probation.retain(|waiting| *waiting != user_id);
+            self.users.insert(user_id, state);
+            None
+        } else if self.users.get(&user_id) == Some(&UserState::Will) {
+            Some(Rsvp::Joined)
+        } else if self.probation.contains(&user_id) {
+            Some(Rsvp::Probation)
+        } else if let Some(position) = self.waitlist.iter().position(|u| *u == user_id) {
+            Some(Rsvp::Waitlisted(position + 1))
+        } else if on_probation && !self.is_started() {
+            self.users.remove(&user_id);
+            self.waitlist.push(user_id);
+            self.probation.push(user_id);
+            Some(Rsvp::Probation)
+        } else if !self.is_full() {
+            self.users.insert(user_id, UserState::Will);
+            Some(Rsvp::Joined)
+        } else {
+            self.users.remove(&user_id);
+            self.waitlist.push(user_id);
+            Some(Rsvp::Waitlisted(self.waitlist.len()))
+        };
+
+        (rsvp, self.promote_waitlist())
+    }
+
+    /// Moves users off the waitlist while there is room, skipping the ones on probation
+    /// until the session has started. Returns the users who were promoted
+    pub fn promote_waitlist(&mut self) -> Vec<UserId> {
+        let is_started = self.is_started();
+        let mut promoted = vec![];
+        while !self.is_full() {
+            let position = match self
+                .waitlist
+                .iter()
+                .position(|user_id| is_started || !self.probation.contains(user_id))
+            {
+                Some(position) => position,
+                None => break,
+            };
+
+            let user_id = self.waitlist.remove(position);
+            self.probation.retain(|waiting| *waiting != user_id);
+            self.users.insert(user_id, UserState::Will);
+            promoted.push(user_id);
+        }
+
+        promoted
+    }
+
+    /// Records that the user is in the voice channel, returns false if that was already known
+    pub fn join_voice(&mut self, user_id: UserId, now: DateTime<Utc>) -> bool {
+        let stints = self.attendance.entry(user_id).or_default();
+        if stints.last().is_some_and(|stint| stint.left.is_none()) {
+            return false;
+        }
+
+        stints.push(VoiceStint {
+            joined: now,
+            left: None,
+        });
+        true
+    }
+
+    /// Records that the user is not in the voice channel, returns false if that was already known
+    pub fn leave_voice(&mut self, user_id: UserId, now: DateTime<Utc>) -> bool {
+        match self
+            .attendance
+            .get_mut(&user_id)
+            .and_then(|stints| stints.last_mut())
+        {
+            Some(stint) if stint.left.is_none() => {
+                stint.left = Some(now);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Total time the user spent in the voice channel up to `now`
+    pub fn time_in_voice(&self, user_id: UserId, now: DateTime<Utc>) -> Duration {
+        self.attendance
+            .get(&user_id)
+            .map(|stints| {
+                stints
+                    .iter()
+                    .fold(Duration::zero(), |total, stint| total + stint.duration(now))
+            })
+            .unwrap_or_else(Duration::zero)
+    }
 }
@@ -0,0 +1,66 @@
+use std::{borrow::Cow, fs, io::ErrorKind, path::Path};
+
+use serde::{de::DeserializeOwned, Serialize};
+use tracing::error;
+
+#[cfg(test)]
+thread_local! {
+    // Each test keeps its state files in its own directory, so tests running at once don't share them
+    pub static TEST_DIR: std::cell::RefCell<Option<std::path::PathBuf>> =
+        const { std::cell::RefCell::new(None) };
+}
+
+fn resolve(path: &Path) -> Cow<'_, Path> {
+    #[cfg(test)]
+    if let Some(dir) = TEST_DIR.with(|dir| dir.borrow().clone()) {
+        return Cow::Owned(dir.join(path));
+    }
+
+    Cow::Borrowed(path)
+}
+
+pub fn read_from<T: DeserializeOwned>(path: &Path) -> Option<T> {
+    let path = &*resolve(path);
+    let state_str = match fs::read_to_string(path) {
+        Ok(s) => s,
+        Err(why) if why.kind() == ErrorKind::NotFound => return None,
+        Err(why) => {
+            error!("Error reading {:?}: {}", path, why);
+            return None;
+        }
+    };
+
+    match serde_json::from_str(&state_str) {
+        Ok(state) => Some(state),
+        Err(why) => {
+            error!("Error parsing {:?}: {}", path, why);
+            None
+        }
+    }
+}
+
+pub fn write_to<T: Serialize>(path: &Path, state: &T) {
+    let path = &*resolve(path);
+    let state_str = match serde_json::to_string_pretty(state) {
+        Ok(s) => s,
+        Err(why) => {
+            error!("Error serializing state for {:?}: {}", path, why);
+            return;
+        }
+    };
+
+    // Write to a temporary file first so a crash mid-write never leaves a truncated state file
+    let tmp_path = path.with_extension("tmp");
+    if let Err(why) = fs::write(&tmp_path, state_str).and_then(|_| fs::rename(&tmp_path, path)) {
+        error!("Error writing {:?}: {}", path, why);
+    }
+}
+
+pub fn remove(path: &Path) {
+    let path = &*resolve(path);
+    if let Err(why) = fs::remove_file(path) {
+        if why.kind() != ErrorKind::NotFound {
+            error!("Error removing {:?}: {}", path, why);
+        }
+    }
+}
@@ -0,0 +1,199 @@
+use std::collections::HashMap;
+
+use serenity::model::{
+    channel::ChannelType,
+    id::{ChannelId, GuildId, RoleId},
+};
+
+use super::{Harness, CONFIG, GAME_CHANNEL, VOICE_CHANNEL};
+use crate::{
+    config::{ColorRole, Config, NoShowPolicy},
+    config_check,
+    context_ext::ContextExt,
+    discord::GuildInfo,
+};
+
+const LOG_CHANNEL: ChannelId = ChannelId(40);
+
+// A guild with everything the harness config refers to, and a log channel
+async fn harness_with_guild() -> Harness {
+    let harness = Harness::new().await;
+    harness.discord.state().guild = Some(GuildInfo {
+        channels: HashMap::from([
+            (GAME_CHANNEL, ChannelType::Text),
+            (VOICE_CHANNEL, ChannelType::Voice),
+            (LOG_CHANNEL, ChannelType::Text),
+        ]),
+        roles: vec![RoleId(20)],
+    });
+
+    let mut config = harness.ctx.config().await;
+    config.admin_log_channel = Some(LOG_CHANNEL);
+    config_check::reload(&harness.ctx.data, config).await;
+    harness
+}
+
+#[tokio::test]
+async fn reloads_with_missing_ids_are_rejected() {
+    let harness = harness_with_guild().await;
+    assert!(harness.discord.contents(LOG_CHANNEL).is_empty());
+
+    let mut config = harness.ctx.config().await;
+    config.games[0].role_id = RoleId(21);
+    config.default_time = "8pm".to_string();
+    config_check::reload(&harness.ctx.data, config).await;
+
+    assert_eq!(harness.ctx.config().await.games[0].role_id, RoleId(20));
+    let contents = harness.discord.contents(LOG_CHANNEL);
+    assert_eq!(contents.len(), 1);
+    assert!(contents[0].starts_with("**config.toml was not reloaded**"));
+    assert!(contents[0].contains("error: default_time: \"8pm\" is not a time"));
+    assert!(contents[0].contains("error: games[0].role_id: there is no role 21 in the guild"));
+}
+
+#[tokio::test]
+async fn reloads_with_only_warnings_are_applied() {
+    let harness = harness_with_guild().await;
+
+    let mut config = harness.ctx.config().await;
+    config.idle_text = "Nothing going on".to_string();
+    config.colors.push(ColorRole {
+        name: "Red".to_string(),
+        role_id: RoleId(22),
+    });
+    config.no_show_policy = Some(NoShowPolicy {
+        max_no_shows: 3,
+        period: "a month".to_string(),
+    });
+    config_check::reload(&harness.ctx.data, config).await;
+
+    assert_eq!(harness.ctx.config().await.idle_text, "Nothing going on");
+    let contents = harness.discord.contents(LOG_CHANNEL);
+    assert_eq!(contents.len(), 1);
+    assert!(contents[0].contains("warning: colors[0].role_id: there is no role 22 in the guild"));
+    assert!(contents[0].contains("warning: no_show_policy.period"));
+}
+
+#[tokio::test]
+async fn reloads_before_the_guild_is_cached_only_check_a_new_guild() {
+    let harness = harness_with_guild().await;
+    harness.discord.state().guild = None;
+
+    let mut config = harness.ctx.config().await;
+    config.idle_text = "Nothing going on".to_string();
+    config_check::reload(&harness.ctx.data, config).await;
+    assert_eq!(harness.ctx.config().await.idle_text, "Nothing going on");
+    let contents = harness.discord.contents(LOG_CHANNEL);
+    assert!(contents[0].contains("warning: guild_id: guild 2 isn't cached yet"));
+
+    let mut config = harness.ctx.config().await;
+    config.guild_id = GuildId(3);
+    config_check::reload(&harness.ctx.data, config).await;
+    assert_eq!(harness.ctx.config().await.guild_id, GuildId(2));
+    let contents = harness.discord.contents(LOG_CHANNEL);
+    assert!(contents[1].contains("error: guild_id: the bot is not in guild 3"));
+}
+
+#[test]
+fn offline_checks_catch_what_discord_would_reject() {
+    let extra = format!(
+        r#"
+[[games]]
+name = "Among Us"
+role_id = 20
+
+[[games]]
+name = "{}"
+role_id = 21
+"#,
+        "Red".repeat(40)
+    );
+    let config: Config =
+        toml::from_str(&format!("{}{}", CONFIG, extra)).expect("Error parsing test config");
+
+    let report = config_check::check(&config);
+    let problems = report
+        .problems
+        .iter()
+        .map(|problem| problem.to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        problems,
+        [
+            "error: games[1].name: \"Among Us\" is already the name of games[0]",
+            "error: games[2].name: is 120 characters long, but a menu option can only show 100",
+            "warning: games[1].role_id: role 20 is already used by games[0].role_id",
+        ]
+    );
+    assert!(report.has_errors());
+}
+
+#[test]
+fn problems_are_located_in_the_config_file() {
+    let source = format!(
+        "{}{}",
+        CONFIG,
+        r#"
+[[games.reminders]]
+offset = "1h before"
+message = "Soon!"
+
+[[games]]
+name = "Minecraft"
+role_id = 21
+reminders = [{ offset = "5m after", message = "Late!" }]
+"#
+    );
+    let lines = config_check::field_lines(&source);
+    let line_of = |text: &str| source.lines().position(|line| line == text).unwrap() + 1;
+
+    assert_eq!(
+        config_check::locate(&lines, "default_time"),
+        Some(line_of("default_time = \"20:00\""))
+    );
+    assert_eq!(
+        config_check::locate(&lines, "games[0].reminders[0].offset"),
+        Some(line_of("offset = \"1h before\""))
+    );
+    assert_eq!(
+        config_check::locate(&lines, "games[1].role_id"),
+        Some(line_of("role_id = 21"))
+    );
+    // Inline tables are located by the key they're in
+    assert_eq!(
+        config_check::locate(&lines, "games[1].reminders[0].offset"),
+        Some(line_of(
+            "reminders = [{ offset = \"5m after\", message = \"Late!\" }]"
+        ))
+    );
+    assert_eq!(config_check::locate(&lines, "admin_log_channel"), None);
+}
+
+#[test]
+fn configs_without_a_timezone_use_utc() {
+    let source = CONFIG.replace("timezone = \"UTC\"\n", "");
+    assert!(!source.contains("timezone"));
+
+    let config: Config = toml::from_str(&source).expect("Error parsing test config");
+    assert_eq!(config.timezone, chrono_tz::Tz::UTC);
+}
+
+#[test]
+fn no_show_policies_need_to_allow_a_no_show() {
+    let mut config: Config = toml::from_str(CONFIG).expect("Error parsing test config");
+    config.no_show_policy = Some(NoShowPolicy {
+        max_no_shows: 0,
+        period: "30d".to_string(),
+    });
+
+    let report = config_check::check(&config);
+    let problems = report
+        .problems
+        .iter()
+        .map(|problem| problem.to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        problems,
+        ["error: no_show_policy.max_no_shows: has to be at least 1, or every Yes would be put on the waitlist"]
+    );
+}
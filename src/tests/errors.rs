@@ -0,0 +1,114 @@
+use serde_json::json;
+use serenity::model::id::MessageId;
+
+use super::{Harness, HOST};
+use crate::{config::Config, context_ext::ContextExt};
+
+#[tokio::test(start_paused = true)]
+async fn forged_menu_values_get_an_error_reply() {
+    let harness = Harness::new().await;
+
+    // The test config has no help pages, so there is nothing the value could point to
+    harness
+        .select(HOST, MessageId(1), "help-dropdown", &["7"])
+        .await;
+    assert_eq!(
+        harness.discord.last_response_content(),
+        "I couldn't make sense of that request!"
+    );
+
+    harness
+        .select(HOST, MessageId(1), "help-dropdown", &["not a number"])
+        .await;
+    assert_eq!(
+        harness.discord.last_response_content(),
+        "I couldn't make sense of that request!"
+    );
+}
+
+#[tokio::test(start_paused = true)]
+async fn huge_stats_periods_are_refused() {
+    let harness = Harness::new().await;
+
+    harness
+        .command(HOST, "stats", &[("period", json!("4294967295d"))])
+        .await;
+    assert_eq!(
+        harness.discord.last_response_content(),
+        "I couldn't understand that period! Try something like \"7d\" or \"30d\""
+    );
+}
+
+#[tokio::test(start_paused = true)]
+async fn game_votes_only_count_games_which_can_be_voted_on() {
+    let harness = Harness::with_config(
+        r#"
+[[games]]
+name = "Minecraft"
+channel_id = 11
+role_id = 21
+
+[[games]]
+name = "Terraria"
+role_id = 22
+"#,
+    )
+    .await;
+    harness.command(HOST, "gamevote", &[]).await;
+    let vote = harness.announcement();
+
+    // Reordering the games doesn't change what a vote is for
+    let mut config = harness.ctx.config().await;
+    config.games.reverse();
+    *harness
+        .ctx
+        .data
+        .read()
+        .await
+        .get::<Config>()
+        .unwrap()
+        .write()
+        .await = config;
+
+    harness
+        .select(HOST, vote, "gamevote-dropdown", &["Among Us"])
+        .await;
+    let content = harness.discord.last_response()["content"].clone();
+    assert!(content.as_str().unwrap().contains("Among Us: 1 vote(s)"));
+
+    // Terraria has no channel to host it in, and values are names rather than indices
+    for value in ["Terraria", "0"] {
+        harness
+            .select(HOST, vote, "gamevote-dropdown", &[value])
+            .await;
+        assert_eq!(
+            harness.discord.last_response_content(),
+            "That game doesn't exist anymore!"
+        );
+    }
+}
+
+#[tokio::test(start_paused = true)]
+async fn forged_poll_votes_get_an_error_reply() {
+    let harness = Harness::new().await;
+    harness
+        .command(HOST, "pollgame", &[("times", json!("in 2h, in 3h"))])
+        .await;
+    assert_eq!(harness.discord.last_response_content(), "Poll posted!");
+    let poll = harness.announcement();
+
+    // There are only two times, and each can only be voted for once
+    for values in [&["0", "2"][..], &["1", "1"], &["one"]] {
+        harness.select(HOST, poll, "poll-dropdown", values).await;
+        assert_eq!(
+            harness.discord.last_response_content(),
+            "I couldn't make sense of that request!"
+        );
+    }
+
+    harness
+        .select(HOST, poll, "poll-dropdown", &["0", "1"])
+        .await;
+    let content = harness.discord.last_response()["content"].clone();
+    assert_eq!(content.as_str().unwrap().matches(": 1 vote(s)").count(), 2);
+}
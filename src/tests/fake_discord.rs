@@ -0,0 +1,356 @@
+use std::{
+    collections::HashMap,
+    sync::{Mutex, MutexGuard},
+};
+
+use serde_json::json;
+use serenity::{
+    async_trait,
+    http::{
+        error::{DiscordJsonError, ErrorResponse},
+        HttpError, StatusCode,
+    },
+    model::id::{ChannelId, GuildId, InteractionId, MessageId, RoleId, UserId},
+    utils::Colour,
+    Error, Result,
+};
+
+use crate::discord::{
+    Discord, File, GuildInfo, JsonMap, MemberInfo, UNKNOWN_CHANNEL, UNKNOWN_MESSAGE,
+};
+
+// The error Discord answers with when asked about a channel or message which doesn't exist
+fn unknown(code: isize) -> Error {
+    let error: DiscordJsonError =
+        serde_json::from_value(json!({ "code": code, "message": "Unknown" })).unwrap();
+    HttpError::UnsuccessfulRequest(ErrorResponse {
+        status_code: StatusCode::NOT_FOUND,
+        url: "https://discord.com/api/v9".parse().unwrap(),
+        error,
+    })
+    .into()
+}
+
+pub struct SentMessage {
+    pub channel_id: ChannelId,
+    pub message_id: MessageId,
+    // The message as it looks after every edit
+    pub message: JsonMap,
+    pub pinned: bool,
+}
+
+impl SentMessage {
+    pub fn content(&self) -> &str {
+        self.message
+            .get("content")
+            .and_then(|content| content.as_str())
+            .unwrap_or_default()
+    }
+}
+
+#[derive(Default)]
+pub struct State {
+    next_id: u64,
+    pub messages: Vec<SentMessage>,
+    pub dms: Vec<(UserId, JsonMap)>,
+    pub responses: Vec<(InteractionId, JsonMap)>,
+    pub followups: Vec<(String, JsonMap, Vec<File>)>,
+    pub members: HashMap<UserId, MemberInfo>,
+    pub voice: HashMap<ChannelId, Vec<UserId>>,
+    // None until a test sets it, like a guild which isn't cached yet
+    pub guild: Option<GuildInfo>,
+    pub role_colours: HashMap<RoleId, Colour>,
+    // The channels the bot created and hasn't deleted yet
+    pub channels: HashMap<ChannelId, JsonMap>,
+    // Scheduled events as they look after every edit, along with the users interested in them
+    pub events: HashMap<u64, JsonMap>,
+    pub event_users: HashMap<u64, Vec<UserId>>,
+    // Makes message edits fail like they do while Discord is having trouble
+    pub failing_edits: bool,
+}
+
+impl State {
+    fn next_id(&mut self) -> u64 {
+        self.next_id += 1;
+        self.next_id
+    }
+}
+
+// Much smaller than Discord's pages, so a handful of interested users takes several pages
+const EVENT_USERS_PAGE: usize = 2;
+
+// The bot's own user, which is also the author of the messages in simulated interactions
+pub const BOT_USER: UserId = UserId(1);
+
+/// Keeps everything the bot does in memory, so tests can look at it afterwards
+#[derive(Default)]
+pub struct FakeDiscord {
+    state: Mutex<State>,
+}
+
+impl FakeDiscord {
+    pub fn state(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap()
+    }
+
+    pub fn add_member(&self, user_id: UserId, name: &str, roles: Vec<RoleId>) {
+        let member = MemberInfo {
+            name: name.to_string(),
+            avatar_url: format!("https://cdn.discordapp.com/avatars/{}.png", user_id),
+            roles,
+        };
+        self.state().members.insert(user_id, member);
+    }
+
+    pub fn set_voice(&self, channel_id: ChannelId, users: Vec<UserId>) {
+        self.state().voice.insert(channel_id, users);
+    }
+
+    /// The content of the messages sent to a channel, oldest first
+    pub fn contents(&self, channel_id: ChannelId) -> Vec<String> {
+        self.state()
+            .messages
+            .iter()
+            .filter(|message| message.channel_id == channel_id)
+            .map(|message| message.content().to_string())
+            .collect()
+    }
+
+    /// The data of the last interaction response, where its content and embeds are
+    pub fn last_response(&self) -> JsonMap {
+        let state = self.state();
+        let (_, response) = state.responses.last().expect("No interaction response");
+        response
+            .get("data")
+            .and_then(|data| data.as_object())
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    pub fn last_response_content(&self) -> String {
+        self.last_response()
+            .get("content")
+            .and_then(|content| content.as_str())
+            .unwrap_or_default()
+            .to_string()
+    }
+}
+
+#[async_trait]
+impl Discord for FakeDiscord {
+    async fn send_message_json(
+        &self,
+        channel_id: ChannelId,
+        message: JsonMap,
+    ) -> Result<MessageId> {
+        let mut state = self.state();
+        let message_id = MessageId(state.next_id());
+        state.messages.push(SentMessage {
+            channel_id,
+            message_id,
+            message,
+            pinned: false,
+        });
+        Ok(message_id)
+    }
+
+    async fn edit_message_json(
+        &self,
+        channel_id: ChannelId,
+        message_id: MessageId,
+        message: JsonMap,
+    ) -> Result<()> {
+        let mut state = self.state();
+        if state.failing_edits {
+            return Err(Error::Other("Service unavailable"));
+        }
+        let sent = state
+            .messages
+            .iter_mut()
+            .find(|sent| sent.channel_id == channel_id && sent.message_id == message_id)
+            .ok_or_else(|| unknown(UNKNOWN_MESSAGE))?;
+
+        // Like Discord, only the fields which are part of the edit change
+        sent.message.extend(message);
+        Ok(())
+    }
+
+    async fn delete_message(&self, channel_id: ChannelId, message_id: MessageId) -> Result<()> {
+        let mut state = self.state();
+        let len = state.messages.len();
+        state
+            .messages
+            .retain(|sent| !(sent.channel_id == channel_id && sent.message_id == message_id));
+        if state.messages.len() == len {
+            return Err(unknown(UNKNOWN_MESSAGE));
+        }
+        Ok(())
+    }
+
+    async fn pin_message(&self, channel_id: ChannelId, message_id: MessageId) -> Result<()> {
+        let mut state = self.state();
+        let sent = state
+            .messages
+            .iter_mut()
+            .find(|sent| sent.channel_id == channel_id && sent.message_id == message_id)
+            .ok_or_else(|| unknown(UNKNOWN_MESSAGE))?;
+        sent.pinned = true;
+        Ok(())
+    }
+
+    async fn unpin_message(&self, channel_id: ChannelId, message_id: MessageId) -> Result<()> {
+        let mut state = self.state();
+        let sent = state
+            .messages
+            .iter_mut()
+            .find(|sent| sent.channel_id == channel_id && sent.message_id == message_id)
+            .ok_or_else(|| unknown(UNKNOWN_MESSAGE))?;
+        sent.pinned = false;
+        Ok(())
+    }
+
+    async fn send_dm_json(&self, user_id: UserId, message: JsonMap) -> Result<()> {
+        self.state().dms.push((user_id, message));
+        Ok(())
+    }
+
+    async fn member(&self, _guild_id: GuildId, user_id: UserId) -> Result<MemberInfo> {
+        self.state()
+            .members
+            .get(&user_id)
+            .cloned()
+            .ok_or(Error::Other("Unknown member"))
+    }
+
+    async fn add_role(&self, _guild_id: GuildId, user_id: UserId, role_id: RoleId) -> Result<()> {
+        let mut state = self.state();
+        let member = state
+            .members
+            .get_mut(&user_id)
+            .ok_or(Error::Other("Unknown member"))?;
+        if !member.roles.contains(&role_id) {
+            member.roles.push(role_id);
+        }
+        Ok(())
+    }
+
+    async fn remove_role(
+        &self,
+        _guild_id: GuildId,
+        user_id: UserId,
+        role_id: RoleId,
+    ) -> Result<()> {
+        let mut state = self.state();
+        let member = state
+            .members
+            .get_mut(&user_id)
+            .ok_or(Error::Other("Unknown member"))?;
+        member.roles.retain(|role| *role != role_id);
+        Ok(())
+    }
+
+    async fn voice_members(
+        &self,
+        _guild_id: GuildId,
+        channel_id: ChannelId,
+    ) -> Option<Vec<UserId>> {
+        Some(
+            self.state()
+                .voice
+                .get(&channel_id)
+                .cloned()
+                .unwrap_or_default(),
+        )
+    }
+
+    async fn guild(&self, _guild_id: GuildId) -> Option<GuildInfo> {
+        self.state().guild.clone()
+    }
+
+    async fn role_colour(&self, _guild_id: GuildId, role_id: RoleId) -> Option<Colour> {
+        self.state().role_colours.get(&role_id).copied()
+    }
+
+    async fn current_user_id(&self) -> UserId {
+        BOT_USER
+    }
+
+    async fn create_channel_json(&self, _guild_id: GuildId, channel: JsonMap) -> Result<ChannelId> {
+        let mut state = self.state();
+        let channel_id = ChannelId(state.next_id());
+        state.channels.insert(channel_id, channel);
+        Ok(channel_id)
+    }
+
+    async fn delete_channel(&self, channel_id: ChannelId) -> Result<()> {
+        self.state()
+            .channels
+            .remove(&channel_id)
+            .map(|_| ())
+            .ok_or_else(|| unknown(UNKNOWN_CHANNEL))
+    }
+
+    async fn create_scheduled_event_json(&self, _guild_id: GuildId, event: JsonMap) -> Result<u64> {
+        let mut state = self.state();
+        let event_id = state.next_id();
+        state.events.insert(event_id, event);
+        Ok(event_id)
+    }
+
+    async fn edit_scheduled_event_json(
+        &self,
+        _guild_id: GuildId,
+        event_id: u64,
+        event: JsonMap,
+    ) -> Result<()> {
+        self.state()
+            .events
+            .get_mut(&event_id)
+            .ok_or(Error::Other("Unknown scheduled event"))?
+            .extend(event);
+        Ok(())
+    }
+
+    async fn scheduled_event_users(
+        &self,
+        _guild_id: GuildId,
+        event_id: u64,
+        after: Option<UserId>,
+    ) -> Result<Vec<UserId>> {
+        let mut users = self
+            .state()
+            .event_users
+            .get(&event_id)
+            .cloned()
+            .unwrap_or_default();
+        users.sort();
+
+        Ok(users
+            .into_iter()
+            .filter(|user_id| after.is_none_or(|after| *user_id > after))
+            .take(EVENT_USERS_PAGE)
+            .collect())
+    }
+
+    async fn create_interaction_response_json(
+        &self,
+        interaction_id: InteractionId,
+        _token: &str,
+        response: JsonMap,
+    ) -> Result<()> {
+        self.state().responses.push((interaction_id, response));
+        Ok(())
+    }
+
+    async fn create_followup_message_json(
+        &self,
+        token: &str,
+        message: JsonMap,
+        files: Vec<File>,
+    ) -> Result<()> {
+        self.state()
+            .followups
+            .push((token.to_string(), message, files));
+        Ok(())
+    }
+}
@@ -0,0 +1,340 @@
+//! Runs the bot's features against an in-memory Discord, with interactions simulated the way the
+//! gateway delivers them and tokio's clock paused, so sessions can be fast-forwarded to their start
+
+mod config_check;
+mod errors;
+mod fake_discord;
+mod sessions;
+
+use std::{
+    collections::HashMap,
+    fs,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
+
+use futures::channel::mpsc::{self, UnboundedReceiver};
+use serde_json::{json, Value};
+use serenity::{
+    cache::Cache,
+    client::{bridge::gateway::ShardMessenger, Context, EventHandler},
+    gateway::InterMessage,
+    http::Http,
+    model::{
+        id::{ChannelId, MessageId, UserId},
+        interactions::Interaction,
+    },
+    prelude::{RwLock, TypeMap},
+};
+
+use crate::{
+    commands::{
+        calendar::CalendarButton, cohost::TransferHost, endhost, gamevote, help, hostgame,
+        pollgame, reschedule::Reschedule, stats::Stats, status::Status, timezone::UserTimezones,
+    },
+    config::Config,
+    discord::DiscordBackend,
+    history::History,
+    interaction_handler::{register_handler, Handler, InteractionMap},
+    poll::Polls,
+    scheduler::ScheduleState,
+    session::SessionMap,
+    store, ClientHandler,
+};
+
+use fake_discord::FakeDiscord;
+
+pub const GAME_CHANNEL: ChannelId = ChannelId(10);
+pub const VOICE_CHANNEL: ChannelId = ChannelId(30);
+pub const HOST: UserId = UserId(100);
+
+const CONFIG: &str = r#"
+application_id = 1
+discord_token = "token"
+guild_id = 2
+vc_channel = 30
+default_time = "20:00"
+idle_text = "No session right now!"
+timezone = "UTC"
+help = []
+colors = []
+
+[ip_embed]
+title = "Server IPs"
+description = ""
+colour = 0
+sections = []
+
+[default_help]
+title = "Help"
+description = ""
+colour = 0
+sections = []
+
+[[games]]
+name = "Among Us"
+channel_id = 10
+role_id = 20
+"#;
+
+// Tells the test directories of tests running at the same time apart
+static NEXT_TEST: AtomicU64 = AtomicU64::new(0);
+
+pub struct Harness {
+    pub ctx: Context,
+    pub discord: Arc<FakeDiscord>,
+    next_id: AtomicU64,
+    dir: PathBuf,
+    // Presence updates are sent to the shard, which has to stay around for them to go through
+    _shard: UnboundedReceiver<InterMessage>,
+}
+
+impl Harness {
+    pub async fn new() -> Self {
+        Self::with_config("").await
+    }
+
+    /// A bot whose config has `extra` appended, which can only add tables
+    pub async fn with_config(extra: &str) -> Self {
+        let dir = std::env::temp_dir().join(format!(
+            "session-bot-test-{}-{}",
+            std::process::id(),
+            NEXT_TEST.fetch_add(1, Ordering::Relaxed)
+        ));
+        fs::create_dir_all(&dir).expect("Error creating test directory");
+        store::TEST_DIR.with(|test_dir| *test_dir.borrow_mut() = Some(dir.clone()));
+
+        let config: Config =
+            toml::from_str(&format!("{}{}", CONFIG, extra)).expect("Error parsing test config");
+        let discord = Arc::new(FakeDiscord::default());
+
+        let mut data = TypeMap::new();
+        data.insert::<Config>(Arc::new(RwLock::new(config)));
+        data.insert::<DiscordBackend>(discord.clone());
+        data.insert::<SessionMap>(Arc::new(RwLock::new(HashMap::new())));
+        data.insert::<hostgame::PendingSessionMap>(HashMap::new());
+        data.insert::<UserTimezones>(Arc::new(RwLock::new(HashMap::new())));
+        data.insert::<ScheduleState>(Arc::new(RwLock::new(ScheduleState::read())));
+        data.insert::<History>(Arc::new(RwLock::new(History::read())));
+        data.insert::<Polls>(Arc::new(RwLock::new(Polls::read())));
+        data.insert::<InteractionMap>(Arc::new(RwLock::new(HashMap::new())));
+
+        let (tx, rx) = mpsc::unbounded();
+        let ctx = Context {
+            data: Arc::new(RwLock::new(data)),
+            shard: ShardMessenger::new(tx),
+            shard_id: 0,
+            http: Arc::new(Http::new_with_token("token")),
+            cache: Arc::new(Cache::default()),
+        };
+
+        let handlers = vec![
+            Handler::Command(Arc::new(hostgame::HostGame)),
+            Handler::Command(Arc::new(Status)),
+            Handler::Command(Arc::new(endhost::EndHost)),
+            Handler::Command(Arc::new(Stats)),
+            Handler::Command(Arc::new(gamevote::GameVoteCommand)),
+            Handler::Command(Arc::new(TransferHost)),
+            Handler::Command(Arc::new(Reschedule)),
+            Handler::Command(Arc::new(pollgame::PollGame)),
+            Handler::Message(Arc::new(hostgame::ButtonConfirm)),
+            Handler::Message(Arc::new(hostgame::ButtonCancel)),
+            Handler::Message(Arc::new(hostgame::ButtonYes)),
+            Handler::Message(Arc::new(hostgame::ButtonMaybe)),
+            Handler::Message(Arc::new(hostgame::ButtonNo)),
+            Handler::Message(Arc::new(endhost::ButtonYes)),
+            Handler::Message(Arc::new(endhost::ButtonNo)),
+            Handler::Message(Arc::new(help::MenuHandler)),
+            Handler::Message(Arc::new(gamevote::MenuHandler)),
+            Handler::Message(Arc::new(gamevote::ButtonClose)),
+            Handler::Message(Arc::new(CalendarButton)),
+            Handler::Message(Arc::new(pollgame::MenuHandler)),
+        ];
+        for handler in handlers {
+            register_handler(&ctx, handler).await;
+        }
+
+        Self {
+            ctx,
+            discord,
+            next_id: AtomicU64::new(1000),
+            dir,
+            _shard: rx,
+        }
+    }
+
+    fn next_id(&self) -> String {
+        self.next_id.fetch_add(1, Ordering::Relaxed).to_string()
+    }
+
+    fn member(&self, user_id: UserId) -> Value {
+        let roles = self
+            .discord
+            .state()
+            .members
+            .get(&user_id)
+            .map(|member| member.roles.clone())
+            .unwrap_or_default();
+
+        json!({
+            "user": {
+                "id": user_id.to_string(),
+                "username": format!("user{}", user_id),
+                "discriminator": "0001",
+                "avatar": null,
+            },
+            "roles": roles.iter().map(|role| role.to_string()).collect::<Vec<_>>(),
+            "joined_at": "2021-01-01T00:00:00Z",
+            "deaf": false,
+            "mute": false,
+        })
+    }
+
+    async fn dispatch(&self, interaction: Value) {
+        let interaction: Interaction =
+            serde_json::from_value(interaction).expect("Error building test interaction");
+        ClientHandler
+            .interaction_create(self.ctx.clone(), interaction)
+            .await;
+    }
+
+    /// Runs a slash command, with string, integer and boolean options
+    pub async fn command(&self, user_id: UserId, name: &str, options: &[(&str, Value)]) {
+        let options = options
+            .iter()
+            .map(|(name, value)| {
+                let kind = match value {
+                    Value::Bool(_) => 5,
+                    Value::Number(_) => 4,
+                    _ => 3,
+                };
+                json!({ "name": name, "type": kind, "value": value })
+            })
+            .collect::<Vec<_>>();
+
+        self.dispatch_command(user_id, name, options, json!({}))
+            .await;
+    }
+
+    /// Runs a slash command whose only option is the user `target`
+    pub async fn command_with_user(
+        &self,
+        user_id: UserId,
+        name: &str,
+        option: &str,
+        target: UserId,
+    ) {
+        let options = vec![json!({ "name": option, "type": 6, "value": target.to_string() })];
+        let resolved = json!({ "users": { target.to_string(): self.member(target)["user"] } });
+        self.dispatch_command(user_id, name, options, resolved)
+            .await;
+    }
+
+    async fn dispatch_command(
+        &self,
+        user_id: UserId,
+        name: &str,
+        options: Vec<Value>,
+        resolved: Value,
+    ) {
+        self.dispatch(json!({
+            "id": self.next_id(),
+            "application_id": "1",
+            "type": 2,
+            "data": {
+                "id": "1",
+                "name": name,
+                "type": 1,
+                "options": options,
+                "resolved": resolved,
+            },
+            "guild_id": "2",
+            "channel_id": GAME_CHANNEL.to_string(),
+            "member": self.member(user_id),
+            "token": "token",
+            "version": 1,
+            "locale": "en-US",
+        }))
+        .await;
+    }
+
+    /// Clicks a button on a message the bot sent
+    pub async fn click(&self, user_id: UserId, message_id: MessageId, custom_id: &str) {
+        self.select(user_id, message_id, custom_id, &[]).await;
+    }
+
+    /// Picks options in a select menu on a message the bot sent
+    pub async fn select(
+        &self,
+        user_id: UserId,
+        message_id: MessageId,
+        custom_id: &str,
+        values: &[&str],
+    ) {
+        self.dispatch(json!({
+            "id": self.next_id(),
+            "application_id": "1",
+            "type": 3,
+            "data": {
+                "custom_id": custom_id,
+                "component_type": if values.is_empty() { 2 } else { 3 },
+                "values": values,
+            },
+            "message": {
+                "id": message_id.to_string(),
+                "channel_id": GAME_CHANNEL.to_string(),
+                "author": {
+                    "id": "1",
+                    "username": "session-bot",
+                    "discriminator": "0001",
+                    "avatar": null,
+                    "bot": true,
+                },
+                "content": "",
+                "timestamp": "2021-01-01T00:00:00Z",
+                "edited_timestamp": null,
+                "tts": false,
+                "mention_everyone": false,
+                "mentions": [],
+                "mention_roles": [],
+                "attachments": [],
+                "embeds": [],
+                "pinned": false,
+                "type": 0,
+            },
+            "guild_id": "2",
+            "channel_id": GAME_CHANNEL.to_string(),
+            "member": self.member(user_id),
+            "token": "token",
+            "version": 1,
+            "locale": "en-US",
+        }))
+        .await;
+    }
+
+    /// The session's announcement, which is the first message sent to the game channel
+    pub fn announcement(&self) -> MessageId {
+        self.discord
+            .state()
+            .messages
+            .iter()
+            .find(|message| message.channel_id == GAME_CHANNEL)
+            .expect("No session announcement")
+            .message_id
+    }
+
+    /// Lets virtual time pass, running everything the bot would do in the meantime
+    pub async fn wait(&self, duration: chrono::Duration) {
+        tokio::time::sleep(duration.to_std().unwrap()).await;
+    }
+}
+
+impl Drop for Harness {
+    fn drop(&mut self) {
+        store::TEST_DIR.with(|test_dir| *test_dir.borrow_mut() = None);
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
@@ -0,0 +1,560 @@
+use std::{path::Path, sync::Arc};
+
+use chrono::Duration;
+use serde_json::{json, Value};
+use serenity::model::id::{ChannelId, MessageId, RoleId, UserId};
+
+use super::{Harness, GAME_CHANNEL, HOST, VOICE_CHANNEL};
+use crate::{
+    clock,
+    commands::hostgame,
+    config::Config,
+    context_ext::ContextExt,
+    discord::Discord,
+    error::Error,
+    guild_event::import_all_interested,
+    history::History,
+    scheduler,
+    session::{UserState, LEGACY_SESSION_PATH, SESSIONS_PATH},
+    store,
+};
+
+const ALICE: UserId = UserId(101);
+const BOB: UserId = UserId(102);
+const LOG_CHANNEL: ChannelId = ChannelId(40);
+
+// Hosts a session starting in an hour, going through the preview like a user would
+async fn host_session(harness: &Harness) {
+    host_session_with(harness, &[]).await;
+}
+
+// Like host_session, with more /hostgame options
+async fn host_session_with(harness: &Harness, options: &[(&str, Value)]) {
+    let mut options = options.to_vec();
+    options.push(("time", json!("in 1h")));
+    harness.command(HOST, "hostgame", &options).await;
+    assert!(harness
+        .discord
+        .last_response_content()
+        .ends_with("Post it?"));
+
+    // The preview is ephemeral, so its message id doesn't matter
+    harness.click(HOST, MessageId(1), "hostgame-confirm").await;
+    assert_eq!(harness.discord.last_response_content(), "Session posted!");
+}
+
+// The text of every field of the embed in a message or response
+fn embed_text(message: &serde_json::Map<String, Value>) -> String {
+    message
+        .get("embeds")
+        .and_then(|embeds| embeds.as_array())
+        .into_iter()
+        .flatten()
+        .chain(message.get("embed"))
+        .flat_map(|embed| embed["fields"].as_array().cloned().unwrap_or_default())
+        .map(|field| format!("{}: {}", field["name"], field["value"]))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[tokio::test(start_paused = true)]
+async fn hostgame_posts_and_pins_the_announcement() {
+    let harness = Harness::new().await;
+    host_session(&harness).await;
+
+    assert!(harness.ctx.is_session_present(GAME_CHANNEL).await);
+    let state = harness.discord.state();
+    assert_eq!(state.messages.len(), 1);
+    assert!(state.messages[0].pinned);
+    assert!(state.messages[0].content().contains("<@&20>"));
+}
+
+#[tokio::test(start_paused = true)]
+async fn rsvps_show_up_in_the_announcement_and_status() {
+    let harness = Harness::new().await;
+    harness.discord.add_member(HOST, "Host", vec![]);
+    host_session(&harness).await;
+    let announcement = harness.announcement();
+
+    harness.click(ALICE, announcement, "button-yes").await;
+    assert_eq!(
+        harness.discord.last_response_content(),
+        "Thanks for saying yes, <@101>"
+    );
+    harness.click(BOB, announcement, "button-maybe").await;
+
+    // The announcement is only updated once the burst of responses is over
+    harness.wait(Duration::seconds(5)).await;
+    let embed = embed_text(&harness.discord.state().messages[0].message);
+    assert!(embed.contains("People who are sure: 1"));
+    assert!(embed.contains("<@101>"));
+    assert!(embed.contains("<@102>"));
+    // A countdown in the text would go stale between edits, Discord keeps a relative timestamp current
+    assert!(embed.contains(":R> | <t:"));
+    assert!(!embed.contains("Time left until start"));
+
+    harness.command(ALICE, "status", &[]).await;
+    let response = harness.discord.last_response();
+    assert!(embed_text(&response).contains("<@101>"));
+    assert_eq!(response["embeds"][0]["author"]["name"], "Host");
+}
+
+#[tokio::test(start_paused = true)]
+async fn endhost_cancels_a_session_which_has_not_started() {
+    let harness = Harness::new().await;
+    host_session(&harness).await;
+    let announcement = harness.announcement();
+    harness.click(ALICE, announcement, "button-yes").await;
+
+    // Only the host may end it
+    harness.command(BOB, "endhost", &[]).await;
+    assert_eq!(
+        harness.discord.last_response_content(),
+        "You don't have permissions to cancel this session!"
+    );
+
+    harness.command(HOST, "endhost", &[]).await;
+    assert_eq!(
+        harness.discord.last_response_content(),
+        "Are you sure you want to cancel the Session?"
+    );
+    harness.click(HOST, MessageId(1), "endhost-yes").await;
+    assert_eq!(
+        harness.discord.last_response_content(),
+        "Session *has* been cancelled!"
+    );
+
+    assert!(!harness.ctx.is_session_present(GAME_CHANNEL).await);
+    assert!(!harness.discord.state().messages[0].pinned);
+    let contents = harness.discord.contents(GAME_CHANNEL);
+    assert_eq!(
+        contents.last().unwrap(),
+        "<@101>: Among Us Session has been cancelled!"
+    );
+}
+
+#[tokio::test(start_paused = true)]
+async fn sessions_start_on_time_and_track_attendance() {
+    let harness = Harness::new().await;
+    host_session(&harness).await;
+    let announcement = harness.announcement();
+    harness.click(ALICE, announcement, "button-yes").await;
+    harness.click(BOB, announcement, "button-yes").await;
+    harness.discord.set_voice(VOICE_CHANNEL, vec![ALICE]);
+
+    harness.wait(Duration::minutes(55)).await;
+    let contents = harness.discord.contents(GAME_CHANNEL);
+    assert_eq!(contents.last().unwrap(), "<@&20> Session starting soon!");
+    assert!(!harness.ctx.is_session_started(GAME_CHANNEL).await);
+
+    harness.wait(Duration::minutes(6)).await;
+    assert!(harness.ctx.is_session_started(GAME_CHANNEL).await);
+    let contents = harness.discord.contents(GAME_CHANNEL);
+    assert_eq!(
+        contents.last().unwrap(),
+        "Among Us Session has started! 2 people said Yes!"
+    );
+
+    // Bob said Yes but isn't in the voice channel
+    harness.wait(Duration::minutes(10)).await;
+    let contents = harness.discord.contents(GAME_CHANNEL);
+    assert_eq!(
+        contents.last().unwrap(),
+        "<@102> you're late, get in the VC!"
+    );
+
+    harness.command(HOST, "endhost", &[]).await;
+    harness.click(HOST, MessageId(1), "endhost-yes").await;
+    let contents = harness.discord.contents(GAME_CHANNEL);
+    assert!(contents
+        .last()
+        .unwrap()
+        .starts_with("Among Us Session has been ended!"));
+
+    let history = harness
+        .ctx
+        .data
+        .read()
+        .await
+        .get::<History>()
+        .unwrap()
+        .clone();
+    assert_eq!(history.read().await.reliability(ALICE, None), (1, 1));
+    assert_eq!(history.read().await.reliability(BOB, None), (0, 1));
+}
+
+#[tokio::test(start_paused = true)]
+async fn hosting_needs_the_host_capability_when_it_is_restricted() {
+    let harness = Harness::with_config("\n[permissions.host]\nroles = [50]\n").await;
+
+    harness
+        .command(HOST, "hostgame", &[("time", json!("in 1h"))])
+        .await;
+    assert_eq!(
+        harness.discord.last_response_content(),
+        "You do not have permissions to use this command!"
+    );
+
+    harness.discord.add_member(HOST, "Host", vec![RoleId(50)]);
+    host_session(&harness).await;
+    assert!(harness.ctx.is_session_present(GAME_CHANNEL).await);
+}
+
+#[tokio::test(start_paused = true)]
+async fn sessions_saved_before_upgrading_are_restored() {
+    let harness = Harness::new().await;
+    let announcement = harness
+        .discord
+        .send_message_json(GAME_CHANNEL, serde_json::Map::new())
+        .await
+        .unwrap();
+
+    // What a version of the bot which only ran one session at a time saved
+    let time = (clock::now() + Duration::hours(1)).to_rfc3339();
+    let legacy = json!({
+        "game": {
+            "name": "Among Us",
+            "channel_id": GAME_CHANNEL,
+            "role_id": 20,
+            "all_roles_exception": null,
+        },
+        "users": { ALICE.to_string(): "Will" },
+        "time": time,
+        "message_id": announcement,
+        "host": HOST,
+    });
+    store::write_to(Path::new(LEGACY_SESSION_PATH), &legacy);
+
+    hostgame::restore_sessions(&harness.ctx).await;
+
+    let session = harness.ctx.session(GAME_CHANNEL).await.unwrap();
+    assert_eq!(session.read().await.host, HOST);
+    assert!(session.read().await.users.contains_key(&ALICE));
+    assert!(store::read_from::<Value>(Path::new(LEGACY_SESSION_PATH)).is_none());
+    assert!(store::read_from::<Value>(Path::new(SESSIONS_PATH)).is_some());
+}
+
+// Forgets the sessions in memory, leaving only what was saved, like the bot restarting
+async fn restart(harness: &Harness) {
+    for session in harness.ctx.sessions().await {
+        session.read().await.handle.abort();
+        harness.ctx.remove_session(&session).await;
+    }
+}
+
+#[tokio::test(start_paused = true)]
+async fn saved_sessions_are_only_dropped_once_their_message_is_gone() {
+    let harness = Harness::new().await;
+    host_session(&harness).await;
+    let announcement = harness.announcement();
+
+    // Discord having trouble doesn't lose the session
+    restart(&harness).await;
+    harness.discord.state().failing_edits = true;
+    hostgame::restore_sessions(&harness.ctx).await;
+    harness.discord.state().failing_edits = false;
+    let session = harness.ctx.session(GAME_CHANNEL).await.unwrap();
+    assert_eq!(session.read().await.message_id, announcement);
+
+    restart(&harness).await;
+    harness
+        .discord
+        .delete_message(GAME_CHANNEL, announcement)
+        .await
+        .unwrap();
+    hostgame::restore_sessions(&harness.ctx).await;
+    assert!(!harness.ctx.is_session_present(GAME_CHANNEL).await);
+    assert!(store::read_from::<Value>(Path::new(SESSIONS_PATH)).is_none());
+}
+
+#[tokio::test(start_paused = true)]
+async fn scheduled_sessions_which_cant_be_posted_are_reported() {
+    let harness = Harness::with_config(
+        r#"
+[[schedules]]
+game = "Among Us"
+weekdays = ["mon", "tue", "wed", "thu", "fri", "sat", "sun"]
+time = "12:00"
+lead_time = "2d"
+"#,
+    )
+    .await;
+    harness
+        .ctx
+        .data
+        .read()
+        .await
+        .get::<Config>()
+        .unwrap()
+        .write()
+        .await
+        .admin_log_channel = Some(LOG_CHANNEL);
+    host_session(&harness).await;
+
+    scheduler::post_scheduled_sessions(&harness.ctx).await;
+    let reports = harness.discord.contents(LOG_CHANNEL);
+    assert_eq!(reports.len(), 1);
+    assert!(reports[0].starts_with("Skipped the scheduled Among Us session"));
+
+    // The occurrence is handled, so it's only reported once
+    scheduler::post_scheduled_sessions(&harness.ctx).await;
+    assert_eq!(harness.discord.contents(LOG_CHANNEL).len(), 1);
+}
+
+#[tokio::test(start_paused = true)]
+async fn only_the_host_can_hand_the_session_over() {
+    let harness = Harness::new().await;
+    host_session(&harness).await;
+    let session = harness.ctx.session(GAME_CHANNEL).await.unwrap();
+    session.write().await.cohosts.push(ALICE);
+
+    // Co-hosts manage the session, but can't take it over
+    harness
+        .command_with_user(ALICE, "transferhost", "user", ALICE)
+        .await;
+    assert_eq!(
+        harness.discord.last_response_content(),
+        "You don't have permissions to hand this session over!"
+    );
+    assert_eq!(session.read().await.host, HOST);
+
+    harness
+        .command_with_user(HOST, "transferhost", "user", ALICE)
+        .await;
+    assert_eq!(
+        harness.discord.last_response_content(),
+        "<@101> is now hosting the session!"
+    );
+    assert_eq!(session.read().await.host, ALICE);
+    assert_eq!(session.read().await.cohosts, vec![HOST]);
+}
+
+#[tokio::test(start_paused = true)]
+async fn spots_which_open_up_go_to_the_waitlist() {
+    let harness = Harness::new().await;
+    host_session_with(&harness, &[("max_players", json!(1))]).await;
+    let announcement = harness.announcement();
+
+    harness.click(ALICE, announcement, "button-yes").await;
+    harness.click(BOB, announcement, "button-yes").await;
+    assert_eq!(
+        harness.discord.last_response_content(),
+        "The session is full! You're #1 on the waitlist, <@102>, I'll let you know when a spot opens up"
+    );
+
+    harness.click(ALICE, announcement, "button-no").await;
+    let session = harness.ctx.session(GAME_CHANNEL).await.unwrap();
+    assert!(session.read().await.waitlist.is_empty());
+    assert_eq!(session.read().await.player_count(), 1);
+
+    let dms = harness.discord.state().dms.clone();
+    assert_eq!(dms.len(), 1);
+    assert_eq!(dms[0].0, BOB);
+    assert!(dms[0].1["content"]
+        .as_str()
+        .unwrap()
+        .starts_with("A spot opened up in the Among Us session"));
+}
+
+#[tokio::test(start_paused = true)]
+async fn reschedule_moves_the_session_and_its_reminders() {
+    let harness = Harness::new().await;
+    host_session(&harness).await;
+    let announcement = harness.announcement();
+    harness.click(ALICE, announcement, "button-yes").await;
+
+    harness
+        .command(HOST, "reschedule", &[("time", json!("in 3h"))])
+        .await;
+    assert!(harness
+        .discord
+        .last_response_content()
+        .starts_with("The session has been moved to"));
+    let contents = harness.discord.contents(GAME_CHANNEL);
+    assert!(contents
+        .last()
+        .unwrap()
+        .starts_with("<@101> The Among Us session has been moved to"));
+
+    // Nothing happens at the old time
+    harness.wait(Duration::minutes(61)).await;
+    assert!(!harness.ctx.is_session_started(GAME_CHANNEL).await);
+    assert_eq!(harness.discord.contents(GAME_CHANNEL).len(), contents.len());
+
+    harness.wait(Duration::minutes(120)).await;
+    assert!(harness.ctx.is_session_started(GAME_CHANNEL).await);
+    assert_eq!(
+        harness.discord.contents(GAME_CHANNEL).last().unwrap(),
+        "Among Us Session has started! 1 person said Yes!"
+    );
+}
+
+#[tokio::test(start_paused = true)]
+async fn sessions_can_get_their_own_voice_channel_and_scheduled_event() {
+    let harness =
+        Harness::with_config("\n[temp_voice]\ncategory_id = 50\nempty_timeout = \"15m\"\n").await;
+    host_session_with(&harness, &[("voice", json!(true)), ("event", json!(true))]).await;
+
+    let session = harness.ctx.session(GAME_CHANNEL).await.unwrap();
+    let (voice_channel, event_id) = {
+        let session = session.read().await;
+        (
+            session.temp_voice_channel.unwrap(),
+            session.event_id.unwrap(),
+        )
+    };
+    {
+        let state = harness.discord.state();
+        assert_eq!(state.channels[&voice_channel]["name"], "Among Us session");
+        assert_eq!(
+            state.events[&event_id]["channel_id"],
+            voice_channel.to_string()
+        );
+    }
+
+    // People interested in the event are counted as Maybe
+    harness.discord.state().event_users.insert(
+        event_id,
+        vec![BOB, UserId(105), UserId(104), UserId(103), ALICE],
+    );
+    import_all_interested(&harness.ctx).await;
+    // More of them than fit on one page
+    for user_id in [ALICE, BOB, UserId(103), UserId(104), UserId(105)] {
+        assert_eq!(session.read().await.users[&user_id], UserState::May);
+    }
+
+    harness
+        .command(HOST, "reschedule", &[("time", json!("in 3h"))])
+        .await;
+    let time = session.read().await.time;
+    assert_eq!(
+        harness.discord.state().events[&event_id]["scheduled_start_time"],
+        time.to_rfc3339()
+    );
+
+    harness.command(HOST, "endhost", &[]).await;
+    harness.click(HOST, MessageId(1), "endhost-yes").await;
+    let state = harness.discord.state();
+    assert_eq!(state.events[&event_id]["status"], 4);
+    assert!(state.channels.is_empty());
+}
+
+#[tokio::test(start_paused = true)]
+async fn a_session_which_loses_the_race_for_its_channel_is_rolled_back() {
+    let harness =
+        Harness::with_config("\n[temp_voice]\ncategory_id = 50\nempty_timeout = \"15m\"\n").await;
+    host_session(&harness).await;
+    let first = harness.ctx.session(GAME_CHANNEL).await.unwrap();
+
+    // Both were let through before either had taken the channel
+    let request = hostgame::SessionRequest {
+        host: ALICE,
+        time: clock::now() + Duration::hours(2),
+        description: String::new(),
+        max_players: None,
+        temp_voice: true,
+        scheduled_event: true,
+    };
+    let result = hostgame::start_session(&harness.ctx, GAME_CHANNEL, request).await;
+    assert!(matches!(result, Err(Error::SessionRunning(GAME_CHANNEL))));
+
+    let session = harness.ctx.session(GAME_CHANNEL).await.unwrap();
+    assert!(Arc::ptr_eq(&first, &session));
+    let state = harness.discord.state();
+    assert_eq!(state.messages.len(), 1);
+    assert!(state.channels.is_empty());
+    assert_eq!(state.events.len(), 1);
+    assert!(state.events.values().all(|event| event["status"] == 4));
+}
+
+#[tokio::test(start_paused = true)]
+async fn closing_a_game_vote_hosts_the_winner_once() {
+    let harness = Harness::with_config(
+        r#"
+[[games]]
+name = "Minecraft"
+channel_id = 11
+role_id = 21
+"#,
+    )
+    .await;
+    harness.command(HOST, "gamevote", &[]).await;
+    let vote = harness.announcement();
+
+    // A vote which can't be closed yet stays open
+    harness.click(HOST, vote, "gamevote-close").await;
+    assert_eq!(
+        harness.discord.last_response_content(),
+        "Nobody has voted yet!"
+    );
+    harness
+        .select(ALICE, vote, "gamevote-dropdown", &["Among Us"])
+        .await;
+
+    harness.click(HOST, vote, "gamevote-close").await;
+    let session = harness.ctx.session(GAME_CHANNEL).await.unwrap();
+    assert_eq!(session.read().await.users[&ALICE], UserState::Will);
+
+    harness.click(HOST, vote, "gamevote-close").await;
+    assert_eq!(
+        harness.discord.last_response_content(),
+        "This vote is already closed!"
+    );
+    assert!(Arc::ptr_eq(
+        &session,
+        &harness.ctx.session(GAME_CHANNEL).await.unwrap()
+    ));
+}
+
+// The uid of the event in the calendar file the session's button gives out
+async fn calendar_uid(harness: &Harness) -> String {
+    harness
+        .click(ALICE, harness.announcement(), "button-calendar")
+        .await;
+    let state = harness.discord.state();
+    let (_, _, files) = state.followups.last().unwrap();
+    let ics = String::from_utf8(files[0].1.clone()).unwrap();
+    ics.lines()
+        .find_map(|line| line.strip_prefix("UID:"))
+        .unwrap()
+        .to_string()
+}
+
+#[tokio::test(start_paused = true)]
+async fn rescheduling_keeps_the_calendar_event() {
+    let harness = Harness::new().await;
+    host_session(&harness).await;
+    let uid = calendar_uid(&harness).await;
+
+    harness
+        .command(HOST, "reschedule", &[("time", json!("in 3h"))])
+        .await;
+    assert_eq!(calendar_uid(&harness).await, uid);
+}
+
+#[tokio::test(start_paused = true)]
+async fn long_rsvp_lists_fit_in_their_embed_fields() {
+    let harness = Harness::new().await;
+    host_session(&harness).await;
+    let announcement = harness.announcement();
+
+    for user_id in 1000..1200 {
+        harness
+            .click(UserId(user_id), announcement, "button-yes")
+            .await;
+    }
+    harness.wait(Duration::seconds(10)).await;
+
+    let state = harness.discord.state();
+    let message = &state.messages[0].message;
+    let fields = message["embeds"][0]["fields"].as_array().unwrap();
+    let sure = fields
+        .iter()
+        .find(|field| field["name"] == "People who are sure: 200")
+        .unwrap()["value"]
+        .as_str()
+        .unwrap();
+    assert!(sure.chars().count() <= 1024);
+    let shown = sure.matches("<@").count();
+    assert!(sure.ends_with(&format!("…and {} more", 200 - shown)));
+}
@@ -0,0 +1,346 @@
+use std::fmt;
+
+use chrono::{
+    DateTime, Datelike, Duration, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Weekday,
+};
+
+// Durations longer than this are refused, so adding them to a time can't overflow
+pub const MAX_DURATION_DAYS: i64 = 366;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum TimeParseError {
+    Invalid(String),
+    InPast(String),
+}
+
+impl fmt::Display for TimeParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TimeParseError::Invalid(input) => write!(
+                f,
+                "I couldn't understand the time \"{}\"! Try something like \"21:00\", \"friday 20:30\", \
+                 \"2026-11-02 21:00\", \"in 45m\", \"+2h\" or a Discord timestamp",
+                input
+            ),
+            TimeParseError::InPast(input) => write!(f, "The time \"{}\" is in the past!", input),
+        }
+    }
+}
+
+/// Resolves the time given to /hostgame relative to `now`. Accepted formats are:
+/// - `21:00` or `9pm`: the next time the clock shows that time
+/// - `today 21:00`, `tomorrow 21:00`, `friday 20:30`: a day followed by an optional time
+/// - `2026-11-02 21:00`: a full date followed by an optional time
+/// - `in 45m`, `+2h`, `in 1h 30m`: an offset from now
+/// - `<t:1793660400>` or `<t:1793660400:F>`: a Discord timestamp
+///
+/// When a day is given without a time, `default_time` is used
+pub fn parse_session_time<Tz: TimeZone>(
+    input: &str,
+    now: DateTime<Tz>,
+    default_time: Option<NaiveTime>,
+) -> Result<DateTime<Tz>, TimeParseError> {
+    let normalized = input.trim().to_lowercase();
+    let invalid = || TimeParseError::Invalid(input.trim().to_string());
+    let tz = now.timezone();
+
+    if let Some(timestamp) = parse_discord_timestamp(&normalized) {
+        let time = tz
+            .timestamp_opt(timestamp, 0)
+            .single()
+            .ok_or_else(invalid)?;
+        return in_future(time, &now, input);
+    }
+
+    if let Some(offset) = normalized
+        .strip_prefix("in ")
+        .or_else(|| normalized.strip_prefix('+'))
+    {
+        let duration = parse_duration(offset).ok_or_else(invalid)?;
+        return Ok(now + duration);
+    }
+
+    if let Some(time) = parse_clock(&normalized) {
+        let today = now.naive_local().date();
+        let session_time = from_local(&tz, today, time).ok_or_else(invalid)?;
+
+        return if session_time < now {
+            from_local(&tz, today + Duration::days(1), time).ok_or_else(invalid)
+        } else {
+            Ok(session_time)
+        };
+    }
+
+    let (day, time) = match normalized.split_once(char::is_whitespace) {
+        Some((day, time)) => (day, parse_clock(time.trim()).ok_or_else(invalid)?),
+        None => (normalized.as_str(), default_time.ok_or_else(invalid)?),
+    };
+
+    let today = now.naive_local().date();
+    let date = if let Ok(date) = NaiveDate::parse_from_str(day, "%Y-%m-%d") {
+        date
+    } else if day == "today" {
+        today
+    } else if day == "tomorrow" {
+        today + Duration::days(1)
+    } else if let Ok(weekday) = day.parse::<Weekday>() {
+        let days_ahead = (7 + weekday.num_days_from_monday() as i64
+            - today.weekday().num_days_from_monday() as i64)
+            % 7;
+        let date = today + Duration::days(days_ahead);
+
+        // A weekday which is today but whose time has passed means next week
+        match from_local(&tz, date, time) {
+            Some(session_time) if session_time < now => date + Duration::weeks(1),
+            _ => date,
+        }
+    } else {
+        return Err(invalid());
+    };
+
+    let session_time = from_local(&tz, date, time).ok_or_else(invalid)?;
+    in_future(session_time, &now, input)
+}
+
+fn in_future<Tz: TimeZone>(
+    time: DateTime<Tz>,
+    now: &DateTime<Tz>,
+    input: &str,
+) -> Result<DateTime<Tz>, TimeParseError> {
+    if time < *now {
+        Err(TimeParseError::InPast(input.trim().to_string()))
+    } else {
+        Ok(time)
+    }
+}
+
+fn from_local<Tz: TimeZone>(tz: &Tz, date: NaiveDate, time: NaiveTime) -> Option<DateTime<Tz>> {
+    tz.from_local_datetime(&NaiveDateTime::new(date, time))
+        .earliest()
+}
+
+// Parses "21:00", "9:30pm" and "9pm"
+fn parse_clock(input: &str) -> Option<NaiveTime> {
+    if let Ok(time) = NaiveTime::parse_from_str(input, "%H:%M") {
+        return Some(time);
+    }
+
+    let input = input.replace(' ', "");
+    let with_minutes = if input.contains(':') {
+        input
+    } else {
+        let split = input.find(|c: char| !c.is_ascii_digit())?;
+        format!("{}:00{}", &input[..split], &input[split..])
+    };
+
+    NaiveTime::parse_from_str(&with_minutes, "%I:%M%p").ok()
+}
+
+// Parses "<t:1793660400>" and "<t:1793660400:F>"
+fn parse_discord_timestamp(input: &str) -> Option<i64> {
+    let inner = input.strip_prefix("<t:")?.strip_suffix('>')?;
+    let timestamp = inner.split(':').next()?;
+    timestamp.parse().ok()
+}
+
+/// Parses durations such as "45m", "2h", "1h30m", "1 hour 30 minutes" or "2d",
+/// which can be at most [`MAX_DURATION_DAYS`] days long
+pub fn parse_duration(input: &str) -> Option<Duration> {
+    let mut minutes: i64 = 0;
+    let mut rest = input.trim();
+
+    if rest.is_empty() {
+        return None;
+    }
+
+    while !rest.is_empty() {
+        let number_end = rest.find(|c: char| !c.is_ascii_digit())?;
+        if number_end == 0 {
+            return None;
+        }
+        let amount = rest[..number_end].parse::<u32>().ok()? as i64;
+        rest = rest[number_end..].trim_start();
+
+        let unit_end = rest
+            .find(|c: char| !c.is_ascii_alphabetic())
+            .unwrap_or(rest.len());
+        let unit = &rest[..unit_end];
+        rest = rest[unit_end..].trim_start_matches(|c: char| c.is_whitespace() || c == ',');
+
+        let unit_minutes = match unit {
+            "m" | "min" | "mins" | "minute" | "minutes" => 1,
+            "h" | "hr" | "hrs" | "hour" | "hours" => 60,
+            "d" | "day" | "days" => 24 * 60,
+            _ => return None,
+        };
+        minutes = amount
+            .checked_mul(unit_minutes)
+            .and_then(|amount| minutes.checked_add(amount))
+            .filter(|minutes| *minutes <= MAX_DURATION_DAYS * 24 * 60)?;
+    }
+
+    Some(Duration::minutes(minutes))
+}
+
+/// Parses offsets relative to a session's start such as "10m before" or "5m after",
+/// with times before the start being negative
+pub fn parse_offset(input: &str) -> Option<Duration> {
+    let input = input.trim().to_lowercase();
+    if let Some(duration) = input.strip_suffix("before") {
+        parse_duration(duration).map(|duration| -duration)
+    } else if let Some(duration) = input.strip_suffix("after") {
+        parse_duration(duration)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{NaiveTime, TimeZone, Utc};
+
+    use super::*;
+
+    // A Wednesday
+    fn now() -> DateTime<Utc> {
+        Utc.ymd(2026, 11, 4).and_hms(18, 0, 0)
+    }
+
+    fn parse(input: &str) -> Result<DateTime<Utc>, TimeParseError> {
+        parse_session_time(input, now(), None)
+    }
+
+    #[test]
+    fn clock_times_are_the_next_time_the_clock_shows_them() {
+        assert_eq!(parse("21:00"), Ok(Utc.ymd(2026, 11, 4).and_hms(21, 0, 0)));
+        assert_eq!(parse("9pm"), Ok(Utc.ymd(2026, 11, 4).and_hms(21, 0, 0)));
+        assert_eq!(
+            parse("9:30 pm"),
+            Ok(Utc.ymd(2026, 11, 4).and_hms(21, 30, 0))
+        );
+        assert_eq!(parse("17:00"), Ok(Utc.ymd(2026, 11, 5).and_hms(17, 0, 0)));
+    }
+
+    #[test]
+    fn days_are_followed_by_a_time() {
+        assert_eq!(
+            parse("today 20:00"),
+            Ok(Utc.ymd(2026, 11, 4).and_hms(20, 0, 0))
+        );
+        assert_eq!(
+            parse("tomorrow 9pm"),
+            Ok(Utc.ymd(2026, 11, 5).and_hms(21, 0, 0))
+        );
+        assert_eq!(
+            parse("friday 20:30"),
+            Ok(Utc.ymd(2026, 11, 6).and_hms(20, 30, 0))
+        );
+        assert_eq!(
+            parse("2026-11-10 21:00"),
+            Ok(Utc.ymd(2026, 11, 10).and_hms(21, 0, 0))
+        );
+    }
+
+    #[test]
+    fn todays_weekday_whose_time_passed_means_next_week() {
+        assert_eq!(
+            parse("wednesday 17:00"),
+            Ok(Utc.ymd(2026, 11, 11).and_hms(17, 0, 0))
+        );
+    }
+
+    #[test]
+    fn days_without_a_time_use_the_default_time() {
+        let default_time = NaiveTime::from_hms(20, 0, 0);
+        assert_eq!(
+            parse_session_time("friday", now(), Some(default_time)),
+            Ok(Utc.ymd(2026, 11, 6).and_hms(20, 0, 0))
+        );
+        assert!(matches!(parse("friday"), Err(TimeParseError::Invalid(_))));
+    }
+
+    #[test]
+    fn offsets_are_added_to_now() {
+        assert_eq!(parse("in 45m"), Ok(now() + Duration::minutes(45)));
+        assert_eq!(parse("+2h"), Ok(now() + Duration::hours(2)));
+        assert_eq!(parse("in 1h 30m"), Ok(now() + Duration::minutes(90)));
+    }
+
+    #[test]
+    fn discord_timestamps_are_read() {
+        let time = Utc.ymd(2026, 11, 10).and_hms(21, 0, 0);
+        assert_eq!(parse(&format!("<t:{}>", time.timestamp())), Ok(time));
+        assert_eq!(parse(&format!("<t:{}:F>", time.timestamp())), Ok(time));
+    }
+
+    #[test]
+    fn past_times_are_refused() {
+        assert!(matches!(
+            parse("2026-11-01 21:00"),
+            Err(TimeParseError::InPast(_))
+        ));
+        assert!(matches!(
+            parse(&format!("<t:{}>", now().timestamp() - 60)),
+            Err(TimeParseError::InPast(_))
+        ));
+    }
+
+    #[test]
+    fn gibberish_is_refused() {
+        for input in [
+            "",
+            "soon",
+            "25:00",
+            "someday 21:00",
+            "in",
+            "in 5 weeks",
+            "+-5m",
+        ] {
+            assert!(
+                matches!(parse(input), Err(TimeParseError::Invalid(_))),
+                "{:?} was accepted",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn durations_add_up_their_parts() {
+        assert_eq!(parse_duration("45m"), Some(Duration::minutes(45)));
+        assert_eq!(parse_duration("2h"), Some(Duration::hours(2)));
+        assert_eq!(parse_duration("1h30m"), Some(Duration::minutes(90)));
+        assert_eq!(
+            parse_duration("1 hour, 30 minutes"),
+            Some(Duration::minutes(90))
+        );
+        assert_eq!(parse_duration("2d"), Some(Duration::days(2)));
+    }
+
+    #[test]
+    fn malformed_durations_are_refused() {
+        for input in ["", "  ", "m", "5", "5 weeks", "-5m", "5m x"] {
+            assert_eq!(parse_duration(input), None, "{:?} was accepted", input);
+        }
+    }
+
+    #[test]
+    fn durations_longer_than_the_maximum_are_refused() {
+        assert_eq!(
+            parse_duration("366d"),
+            Some(Duration::days(MAX_DURATION_DAYS))
+        );
+        assert_eq!(parse_duration("366d 1m"), None);
+        assert_eq!(parse_duration("4294967295d"), None);
+        assert_eq!(parse_duration("4294967295m 4294967295m 4294967295m"), None);
+        assert_eq!(parse_duration(&"200d ".repeat(1000)), None);
+        assert!(parse_session_time("in 4294967295d", now(), None).is_err());
+    }
+
+    #[test]
+    fn offsets_are_negative_before_the_start() {
+        assert_eq!(parse_offset("10m before"), Some(-Duration::minutes(10)));
+        assert_eq!(parse_offset("5m After"), Some(Duration::minutes(5)));
+        assert_eq!(parse_offset("10m"), None);
+        assert_eq!(parse_offset("4294967295d before"), None);
+    }
+}
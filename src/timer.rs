@@ -0,0 +1,254 @@
+use std::sync::Arc;
+
+use chrono::{DateTime, Duration, Utc};
+use serenity::{
+    client::Context,
+    model::id::{ChannelId, GuildId, UserId},
+    prelude::RwLock,
+};
+use tokio::task::JoinHandle;
+use tracing::warn;
+
+use crate::{
+    announcement::update_announcement,
+    attendance::{sync_attendance, voice_members},
+    clock,
+    commands::{hostgame::notify_promoted, prelude::update_bot_status, status::get_status_embed},
+    config::{Audience, Reminder},
+    context_ext::ContextExt,
+    guild_event::{set_event_status, EventStatus},
+    session::{Session, UserState},
+    time_parse::parse_offset,
+};
+
+enum EventKind {
+    Reminder(Reminder),
+    Start,
+}
+
+struct Event {
+    time: DateTime<Utc>,
+    kind: EventKind,
+}
+
+async fn sleep_until(time: DateTime<Utc>) {
+    tokio::time::sleep((time - clock::now()).to_std().unwrap_or_default()).await;
+}
+
+// The reminders and the start of a session, in the order they happen. Events whose time has already
+// passed (e.g. after a restart) are dropped, except that a session hosted shortly before it starts
+// still gets the last reminder it missed a minute from now, if no other one comes before the start
+fn session_events(
+    reminders: Vec<Reminder>,
+    session_time: DateTime<Utc>,
+    now: DateTime<Utc>,
+) -> Vec<Event> {
+    let mut events = vec![Event {
+        time: session_time,
+        kind: EventKind::Start,
+    }];
+
+    for reminder in reminders {
+        match parse_offset(&reminder.offset) {
+            Some(offset) => events.push(Event {
+                time: session_time + offset,
+                kind: EventKind::Reminder(reminder),
+            }),
+            None => warn!(
+                "Ignoring reminder with invalid offset \"{}\"",
+                reminder.offset
+            ),
+        }
+    }
+
+    events.sort_by_key(|event| event.time);
+
+    let soon = now + Duration::minutes(1);
+    let is_reminder = |event: &Event| matches!(event.kind, EventKind::Reminder(_));
+    let upcoming_reminder = events
+        .iter()
+        .any(|event| is_reminder(event) && event.time >= now && event.time < session_time);
+    if soon < session_time && !upcoming_reminder {
+        if let Some(last_before_start) = events
+            .iter_mut()
+            .filter(|event| is_reminder(event) && event.time < now)
+            .last()
+        {
+            last_before_start.time = soon;
+        }
+        events.sort_by_key(|event| event.time);
+    }
+
+    events.retain(|event| event.time >= now);
+    events
+}
+
+/// Spawns the task which sends the session's reminders and its start message
+pub fn spawn_session_task(
+    ctx: &Context,
+    channel_id: ChannelId,
+    guild_id: GuildId,
+    session_time: DateTime<Utc>,
+    reminders: Vec<Reminder>,
+) -> JoinHandle<()> {
+    let ctx = ctx.clone();
+    tokio::task::spawn(async move {
+        for event in session_events(reminders, session_time, clock::now()) {
+            sleep_until(event.time).await;
+
+            let session = match ctx.session(channel_id).await {
+                Some(session) => session,
+                None => return,
+            };
+
+            match event.kind {
+                EventKind::Reminder(reminder) => {
+                    send_reminder(&ctx, &session, channel_id, guild_id, &reminder).await
+                }
+                EventKind::Start => send_start(&ctx, &session, channel_id, guild_id).await,
+            }
+        }
+    })
+}
+
+async fn send_start(
+    ctx: &Context,
+    session: &Arc<RwLock<Session>>,
+    channel_id: ChannelId,
+    guild_id: GuildId,
+) {
+    sync_attendance(ctx, session).await;
+
+    // Users on probation get the spots which are left now
+    let promoted = session.write().await.promote_waitlist();
+    ctx.save_sessions().await;
+    if !promoted.is_empty() {
+        let game = session.read().await.game.name.clone();
+        notify_promoted(ctx, channel_id, &game, &promoted).await;
+    }
+
+    let (game, member_amount) = {
+        let session = session.read().await;
+        (session.game.name.clone(), session.player_count())
+    };
+
+    let embed = get_status_embed(ctx, session, guild_id).await;
+    let person_or_people = if member_amount == 1 {
+        "person"
+    } else {
+        "people"
+    };
+
+    if let Err(why) = ctx
+        .discord()
+        .await
+        .send_message(channel_id, |message| {
+            message.set_embed(embed).content(format!(
+                "{} Session has started! {} {} said Yes!",
+                game, member_amount, person_or_people
+            ))
+        })
+        .await
+    {
+        warn!("Error sending message to channel: {}", why);
+    }
+
+    let event_id = session.read().await.event_id;
+    if let Some(event_id) = event_id {
+        set_event_status(ctx, event_id, EventStatus::Active).await;
+    }
+
+    update_bot_status(ctx).await;
+    update_announcement(ctx, session).await;
+}
+
+async fn send_reminder(
+    ctx: &Context,
+    session: &Arc<RwLock<Session>>,
+    channel_id: ChannelId,
+    guild_id: GuildId,
+    reminder: &Reminder,
+) {
+    let (game, time) = {
+        let session = session.read().await;
+        (session.game.clone(), session.time)
+    };
+
+    let users = match reminder.audience {
+        Audience::Role => vec![],
+        Audience::Yes => users_with(session, &[UserState::Will]).await,
+        Audience::YesMaybe => users_with(session, &[UserState::Will, UserState::May]).await,
+        Audience::Late => late_users(ctx, session).await,
+    };
+
+    let pings = match reminder.audience {
+        Audience::Role => format!("<@&{}>", game.role_id),
+        _ if users.is_empty() => return,
+        _ => users
+            .iter()
+            .map(|user_id| format!("<@{}>", user_id))
+            .collect::<Vec<_>>()
+            .join(" "),
+    };
+
+    let content = reminder
+        .message
+        .replace("{game}", &game.name)
+        .replace("{time}", &format!("<t:{}:R>", time.timestamp()))
+        .replace("{pings}", &pings);
+
+    // Reminders sent before the start show how the session looks at that point
+    let embed = if time > clock::now() {
+        Some(get_status_embed(ctx, session, guild_id).await)
+    } else {
+        None
+    };
+
+    if let Err(why) = ctx
+        .discord()
+        .await
+        .send_message(channel_id, |message| {
+            if let Some(embed) = embed {
+                message.set_embed(embed);
+            }
+            message
+                .content(content)
+                .allowed_mentions(|mentions| mentions.roles(vec![game.role_id]).users(users))
+        })
+        .await
+    {
+        warn!("Error sending message to channel: {}", why);
+    }
+
+    update_announcement(ctx, session).await;
+}
+
+async fn users_with(session: &Arc<RwLock<Session>>, states: &[UserState]) -> Vec<UserId> {
+    session
+        .read()
+        .await
+        .users
+        .iter()
+        .filter(|(_, state)| states.contains(state))
+        .map(|(user_id, _)| *user_id)
+        .collect()
+}
+
+// Users who said Yes but aren't in the voice channel
+async fn late_users(ctx: &Context, session: &Arc<RwLock<Session>>) -> Vec<UserId> {
+    let config = ctx.config().await;
+    let vc_channel = session.read().await.voice_channel(&config);
+    let members = match voice_members(ctx, vc_channel).await {
+        Some(members) => members,
+        None => {
+            warn!("Guild is not cached, can't tell who is late");
+            return vec![];
+        }
+    };
+
+    users_with(session, &[UserState::Will])
+        .await
+        .into_iter()
+        .filter(|user_id| !members.contains(user_id))
+        .collect()
+}
@@ -0,0 +1,141 @@
+use std::sync::Arc;
+
+use chrono::Duration;
+use serenity::{
+    client::Context,
+    model::{
+        channel::{ChannelType, PermissionOverwrite, PermissionOverwriteType},
+        id::{ChannelId, RoleId},
+        Permissions,
+    },
+    prelude::RwLock,
+};
+use tracing::{info, warn};
+
+use crate::{
+    announcement::update_announcement,
+    attendance::voice_members,
+    clock,
+    commands::endhost::end_session,
+    config::{Config, Game},
+    context_ext::ContextExt,
+    session::Session,
+    time_parse::parse_duration,
+};
+
+/// Creates a voice channel for a session of `game` which only the game's role can join.
+/// Returns None if temporary voice channels aren't configured or the channel couldn't be created
+pub async fn create_temp_voice(ctx: &Context, config: &Config, game: &Game) -> Option<ChannelId> {
+    let temp_voice = config.temp_voice.as_ref()?;
+    // The @everyone role has the same id as the guild
+    let everyone = RoleId(config.guild_id.0);
+    let permissions = vec![
+        PermissionOverwrite {
+            allow: Permissions::empty(),
+            deny: Permissions::CONNECT,
+            kind: PermissionOverwriteType::Role(everyone),
+        },
+        PermissionOverwrite {
+            allow: Permissions::CONNECT,
+            deny: Permissions::empty(),
+            kind: PermissionOverwriteType::Role(game.role_id),
+        },
+    ];
+
+    match ctx
+        .discord()
+        .await
+        .create_channel(config.guild_id, |channel| {
+            channel
+                .name(format!("{} session", game.name))
+                .kind(ChannelType::Voice)
+                .category(temp_voice.category_id)
+                .permissions(permissions)
+        })
+        .await
+    {
+        Ok(channel_id) => Some(channel_id),
+        Err(why) => {
+            warn!(
+                "Error creating voice channel for {} session: {}",
+                game.name, why
+            );
+            None
+        }
+    }
+}
+
+/// Deletes the voice channel created for the session, if there is one
+pub async fn delete_temp_voice(ctx: &Context, session: &Arc<RwLock<Session>>) {
+    let channel_id = match session.write().await.temp_voice_channel.take() {
+        Some(channel_id) => channel_id,
+        None => return,
+    };
+
+    delete_voice_channel(ctx, channel_id).await;
+}
+
+pub async fn delete_voice_channel(ctx: &Context, channel_id: ChannelId) {
+    if let Err(why) = ctx.discord().await.delete_channel(channel_id).await {
+        warn!("Error deleting session voice channel: {}", why);
+    }
+}
+
+/// Keeps track of how long the voice channels of running sessions have been empty, ending the sessions
+/// and deleting the created channels which have been empty for longer than allowed. A session whose
+/// channel was deleted carries on with the game's usual voice channel
+pub async fn check_voice_channels(ctx: &Context) {
+    let config = ctx.config().await;
+    let delete_after = config
+        .temp_voice
+        .as_ref()
+        .and_then(|temp_voice| parse_duration(&temp_voice.empty_timeout));
+    let end_after = config
+        .auto_end
+        .as_ref()
+        .and_then(|auto_end| auto_end.empty_timeout.as_deref())
+        .and_then(parse_duration);
+
+    if delete_after.is_none() && end_after.is_none() {
+        return;
+    }
+
+    let now = clock::now();
+    for session in ctx.sessions().await {
+        let channel_id = {
+            let session = session.read().await;
+            if !session.is_started() {
+                continue;
+            }
+            session.voice_channel(&config)
+        };
+
+        let is_empty = match voice_members(ctx, channel_id).await {
+            Some(members) => members.is_empty(),
+            None => continue,
+        };
+
+        let (empty_for, has_temp_voice) = {
+            let mut session = session.write().await;
+            let empty_for = if is_empty {
+                now - *session.voice_empty_since.get_or_insert(now)
+            } else {
+                session.voice_empty_since = None;
+                Duration::zero()
+            };
+            (empty_for, session.temp_voice_channel.is_some())
+        };
+
+        if end_after.is_some_and(|end_after| is_empty && empty_for >= end_after) {
+            info!("Ending session whose voice channel has been empty too long");
+            end_session(ctx, &session).await;
+        } else if has_temp_voice
+            && delete_after.is_some_and(|delete_after| is_empty && empty_for >= delete_after)
+        {
+            info!("Deleting session voice channel which has been empty too long");
+            delete_temp_voice(ctx, &session).await;
+            ctx.save_sessions().await;
+            update_announcement(ctx, &session).await;
+        }
+    }
+}